@@ -0,0 +1,706 @@
+use proc_macro2::Span;
+use quote::ToTokens;
+use syn::{
+    Attribute, DeriveInput, Expr, Ident, LitInt, LitStr, Member, Path, Token, Type, TypeParamBound,
+    WherePredicate, bracketed, meta::ParseNestedMeta, punctuated::Punctuated, token,
+};
+
+use crate::bound;
+use crate::context::ContextType;
+
+/// Options collected from the container-level `#[trait_decode(...)]` attributes.
+///
+/// All three derives parse the same attribute so that a single `#[trait_decode]` can be shared
+/// between `Encode`, `Decode` and `BorrowDecodeFromDecode`; every derive only looks at the keys
+/// relevant to it.
+pub(crate) struct ContainerAttrs {
+    /// `crate = path`: the path to bincode used in the generated code, `::bincode` by default.
+    pub krate: Path,
+    /// `trait = A + B`: bounds on the generic context, accumulated over repeated `trait` keys.
+    pub trait_bounds: Vec<TypeParamBound>,
+    /// `context_type = X` or `context_type = [X, Y]`: one impl per concrete context.
+    pub context_types: Vec<ContextType>,
+    /// `context_param = C`: decode with the struct's own type parameter `C` as the context.
+    pub context_param: Option<Ident>,
+    /// `infer`: bound the context by what every concrete field type needs.
+    pub infer: bool,
+    /// `bound(...)`: replaces the inferred where-predicates.
+    pub bound: Bounds,
+    /// `add_bound(...)`: appended to the (inferred or replaced) where-predicates.
+    pub add_bound: Bounds,
+    /// `by_key = field, registry = path, find = method`: encoded as the key field alone.
+    pub by_key: Option<ByKey>,
+    /// `validate = path`: `fn(&Self, &__Context) -> Result<(), String>`, called after decoding.
+    pub validate: Option<Path>,
+    /// `finish = path`: `fn(&mut Self, &mut __Context)`, called after `validate`.
+    pub finish: Option<Path>,
+    /// `pre_encode = path`: `fn(&Self) -> Result<(), String>`, called before encoding.
+    pub pre_encode: Option<Path>,
+    /// `stable_indices`: every variant must be given its index explicitly.
+    pub stable_indices: bool,
+    /// `tag = u8` or `tag = "name"`: how the variant of an enum is written, a `usize` by default.
+    pub tag: Option<Tag>,
+    /// `compat = "bincode_derive"`: written exactly like bincode's own derive would.
+    pub compat: Option<Compat>,
+    /// `length_prefixed`: the fields of every variant are written after their length in bytes,
+    /// so that an `unknown` variant can keep the variants it does not know. A known variant is
+    /// decoded from its payload with a clone of the context, so the context must be `Clone`, and
+    /// bytes after its fields are skipped.
+    pub length_prefixed: bool,
+}
+
+/// How the variant of an enum is written.
+pub(crate) enum Tag {
+    /// `tag = u8`: its index, as the given integer type.
+    Int(IntTag),
+    /// `tag = "name"`: its name as a string, see `VariantAttrs::rename` and `VariantAttrs::alias`.
+    Name(LitStr),
+}
+
+impl Tag {
+    fn parse(meta: &ParseNestedMeta) -> syn::Result<Self> {
+        let value = meta.value()?;
+        if !value.peek(LitStr) {
+            return Ok(Tag::Int(IntTag::parse(value.parse()?)?));
+        }
+        let tag: LitStr = value.parse()?;
+        if tag.value() != "name" {
+            return Err(syn::Error::new_spanned(
+                tag,
+                "unsupported `tag`, use an integer type or \"name\"",
+            ));
+        }
+        Ok(Tag::Name(tag))
+    }
+
+    pub fn span(&self) -> Span {
+        match self {
+            Tag::Int(tag) => tag.ty.span(),
+            Tag::Name(tag) => tag.span(),
+        }
+    }
+}
+
+/// The integer type the discriminant of an enum is written as.
+pub(crate) struct IntTag {
+    pub ty: Ident,
+    /// The largest discriminant the type can hold.
+    pub max: u64,
+}
+
+impl IntTag {
+    fn parse(ty: Ident) -> syn::Result<Self> {
+        let max = match ty.to_string().as_str() {
+            "u8" => u8::MAX.into(),
+            "u16" => u16::MAX.into(),
+            "u32" => u32::MAX.into(),
+            "u64" | "usize" => u64::MAX,
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    ty,
+                    "`tag` must be one of `u8`, `u16`, `u32`, `u64` and `usize`",
+                ));
+            }
+        };
+        Ok(IntTag { ty, max })
+    }
+}
+
+/// A wire format the derives reproduce, from `compat = "..."`.
+#[derive(Clone, Copy, PartialEq)]
+pub(crate) enum Compat {
+    /// Variants are numbered by position and written as a `u32`, as `bincode::Encode` does.
+    BincodeDerive,
+}
+
+/// A type that is written as one of its fields and decoded by finding that key in a registry
+/// of the context, as in `by_key = id, registry = ParticleListTrait::get_particle_list,
+/// find = get_particle_from_id`.
+pub(crate) struct ByKey {
+    /// The field that identifies the value, which must be `Clone + Debug` for the error message.
+    pub key: Member,
+    /// A function from `&__Context` to the registry, the context itself if omitted. Without
+    /// `trait`, a `Trait::method` path bounds a generic context by `Trait`.
+    pub registry: Option<Path>,
+    /// The method of the registry that maps the key to an `Option<Self>`.
+    pub find: Ident,
+}
+
+/// Where-predicates given per derive, as in `bound(encode = "...", decode = "...")`.
+#[derive(Default)]
+pub(crate) struct Bounds {
+    pub encode: Option<Vec<WherePredicate>>,
+    pub decode: Option<Vec<WherePredicate>>,
+    pub borrow_decode: Option<Vec<WherePredicate>>,
+}
+
+impl Bounds {
+    /// `BorrowDecodeFromDecode` forwards to `Decode`, so it falls back to the decode predicates.
+    pub fn for_borrow_decode(&self) -> Option<&Vec<WherePredicate>> {
+        self.borrow_decode.as_ref().or(self.decode.as_ref())
+    }
+
+    fn parse(&mut self, meta: &ParseNestedMeta) -> syn::Result<()> {
+        meta.parse_nested_meta(|meta| {
+            let slot = if meta.path.is_ident("encode") {
+                &mut self.encode
+            } else if meta.path.is_ident("decode") {
+                &mut self.decode
+            } else if meta.path.is_ident("borrow_decode") {
+                &mut self.borrow_decode
+            } else {
+                return Err(meta.error(
+                    "unrecognized bound, supported keys are `encode`, `decode` and `borrow_decode`",
+                ));
+            };
+            if slot.is_some() {
+                return Err(meta.error("duplicate bound"));
+            }
+            let lit: LitStr = meta.value()?.parse()?;
+            let predicates =
+                lit.parse_with(Punctuated::<WherePredicate, Token![,]>::parse_terminated)?;
+            *slot = Some(predicates.into_iter().collect());
+            Ok(())
+        })
+    }
+}
+
+impl ContainerAttrs {
+    pub fn parse(input: &DeriveInput) -> syn::Result<Self> {
+        let mut out = ContainerAttrs {
+            krate: syn::parse_quote! { ::bincode },
+            trait_bounds: Vec::new(),
+            context_types: Vec::new(),
+            context_param: None,
+            infer: false,
+            bound: Bounds::default(),
+            add_bound: Bounds::default(),
+            by_key: None,
+            validate: None,
+            finish: None,
+            pre_encode: None,
+            stable_indices: false,
+            tag: None,
+            compat: None,
+            length_prefixed: false,
+        };
+        let mut krate_set = false;
+        let mut key = None;
+        let mut registry = None;
+        let mut find = None;
+        for attr in input
+            .attrs
+            .iter()
+            .filter(|a| a.path().is_ident("trait_decode"))
+        {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("crate") {
+                    if krate_set {
+                        return Err(meta.error("duplicate `crate` in #[trait_decode]"));
+                    }
+                    out.krate = meta.value()?.call(Path::parse_mod_style)?;
+                    krate_set = true;
+                    Ok(())
+                } else if meta.path.is_ident("trait") {
+                    if !out.context_types.is_empty() {
+                        return Err(meta.error("cannot specify both `trait` and `context_type` in #[trait_decode]"));
+                    }
+                    let bounds = Punctuated::<TypeParamBound, Token![+]>::parse_separated_nonempty(
+                        meta.value()?,
+                    )?;
+                    out.trait_bounds.extend(bounds);
+                    Ok(())
+                } else if meta.path.is_ident("context_type") {
+                    if !out.trait_bounds.is_empty() {
+                        return Err(meta.error("cannot specify both `trait` and `context_type` in #[trait_decode]"));
+                    }
+                    if out.context_param.is_some() {
+                        return Err(meta.error("cannot specify both `context_param` and `context_type` in #[trait_decode]"));
+                    }
+                    let value = meta.value()?;
+                    let context_types = if value.peek(token::Bracket) {
+                        let content;
+                        bracketed!(content in value);
+                        Punctuated::<ContextType, Token![,]>::parse_terminated(&content)?
+                            .into_iter()
+                            .collect()
+                    } else {
+                        vec![value.parse::<ContextType>()?]
+                    };
+                    for context_type in context_types {
+                        let tokens = context_type.ty.to_token_stream().to_string();
+                        if out
+                            .context_types
+                            .iter()
+                            .any(|seen| seen.ty.to_token_stream().to_string() == tokens)
+                        {
+                            return Err(syn::Error::new_spanned(
+                                &context_type.ty,
+                                "duplicate context type in #[trait_decode]",
+                            ));
+                        }
+                        out.context_types.push(context_type);
+                    }
+                    Ok(())
+                } else if meta.path.is_ident("context_param") {
+                    if !out.context_types.is_empty() {
+                        return Err(meta.error("cannot specify both `context_param` and `context_type` in #[trait_decode]"));
+                    }
+                    let param: Ident = meta.value()?.parse()?;
+                    let is_type_param = input
+                        .generics
+                        .type_params()
+                        .any(|type_param| type_param.ident == param);
+                    if !is_type_param {
+                        return Err(syn::Error::new(
+                            param.span(),
+                            format!("`{param}` is not a type parameter of `{}`", input.ident),
+                        ));
+                    }
+                    out.context_param = Some(param);
+                    Ok(())
+                } else if meta.path.is_ident("infer") {
+                    out.infer = true;
+                    Ok(())
+                } else if meta.path.is_ident("stable_indices") {
+                    out.stable_indices = true;
+                    Ok(())
+                } else if meta.path.is_ident("tag") {
+                    let tag = Tag::parse(&meta)?;
+                    set_once(&meta, &mut out.tag, tag)
+                } else if meta.path.is_ident("length_prefixed") {
+                    out.length_prefixed = true;
+                    Ok(())
+                } else if meta.path.is_ident("compat") {
+                    let format: LitStr = meta.value()?.parse()?;
+                    let compat = match format.value().as_str() {
+                        "bincode_derive" => Compat::BincodeDerive,
+                        _ => {
+                            return Err(syn::Error::new_spanned(
+                                format,
+                                "unsupported `compat`, the supported format is \"bincode_derive\"",
+                            ));
+                        }
+                    };
+                    set_once(&meta, &mut out.compat, compat)
+                } else if meta.path.is_ident("bound") {
+                    out.bound.parse(&meta)
+                } else if meta.path.is_ident("add_bound") {
+                    out.add_bound.parse(&meta)
+                } else if meta.path.is_ident("validate") {
+                    set_once(&meta, &mut out.validate, meta.value()?.parse()?)
+                } else if meta.path.is_ident("finish") {
+                    set_once(&meta, &mut out.finish, meta.value()?.parse()?)
+                } else if meta.path.is_ident("pre_encode") {
+                    set_once(&meta, &mut out.pre_encode, meta.value()?.parse()?)
+                } else if meta.path.is_ident("by_key") {
+                    set_once(&meta, &mut key, meta.value()?.parse()?)
+                } else if meta.path.is_ident("registry") {
+                    set_once(&meta, &mut registry, meta.value()?.parse::<Path>()?)
+                } else if meta.path.is_ident("find") {
+                    set_once(&meta, &mut find, meta.value()?.parse()?)
+                } else {
+                    Err(meta.error("unrecognized key for #[trait_decode] attribute, supported keys are `crate`, `trait`, `context_type`, `context_param`, `infer`, `bound`, `add_bound`, `by_key`, `registry`, `find`, `validate`, `finish`, `pre_encode`, `stable_indices`, `tag`, `compat` and `length_prefixed`"))
+                }
+            })?;
+        }
+
+        match (key, find) {
+            (Some(key), Some(find)) => {
+                // `registry = ParticleListTrait::get_particle_list` bounds a generic context by
+                // `ParticleListTrait`, unless the context is given some other way
+                if let Some(bound) = registry.as_ref().and_then(bound::accessor_trait)
+                    && out.trait_bounds.is_empty()
+                    && out.context_types.is_empty()
+                    && out.context_param.is_none()
+                {
+                    out.trait_bounds.push(syn::parse_quote! { #bound });
+                }
+                out.by_key = Some(ByKey {
+                    key,
+                    registry,
+                    find,
+                });
+            }
+            (None, None) if registry.is_none() => {}
+            (Some(_), None) => {
+                return Err(syn::Error::new(
+                    Span::call_site(),
+                    "`by_key` needs `find` in #[trait_decode]",
+                ));
+            }
+            _ => {
+                return Err(syn::Error::new(
+                    Span::call_site(),
+                    "`registry` and `find` need `by_key` in #[trait_decode]",
+                ));
+            }
+        }
+
+        if out.compat == Some(Compat::BincodeDerive) {
+            if out.stable_indices {
+                return Err(syn::Error::new(
+                    Span::call_site(),
+                    "`compat = \"bincode_derive\"` numbers variants by position and cannot be combined with `stable_indices`",
+                ));
+            }
+            if out.length_prefixed {
+                return Err(syn::Error::new(
+                    Span::call_site(),
+                    "`compat = \"bincode_derive\"` cannot be combined with `length_prefixed`",
+                ));
+            }
+            match &out.tag {
+                Some(Tag::Int(tag)) if tag.ty == "u32" => {}
+                Some(tag) => {
+                    return Err(syn::Error::new(
+                        tag.span(),
+                        "`compat = \"bincode_derive\"` writes the discriminant as a `u32`",
+                    ));
+                }
+                None => {}
+            }
+        }
+        if out.stable_indices && out.by_name() {
+            return Err(syn::Error::new(
+                Span::call_site(),
+                "`stable_indices` has no effect with `tag = \"name\"`",
+            ));
+        }
+        Ok(out)
+    }
+
+    /// Whether the variants of an enum are written by name, with `tag = "name"`.
+    pub fn by_name(&self) -> bool {
+        matches!(self.tag, Some(Tag::Name(_)))
+    }
+
+    /// The integer type the discriminant of an enum is written as, unless it is written by name.
+    pub fn int_tag(&self) -> IntTag {
+        match (&self.tag, self.compat) {
+            (Some(Tag::Int(tag)), _) => IntTag {
+                ty: tag.ty.clone(),
+                max: tag.max,
+            },
+            (_, Some(Compat::BincodeDerive)) => IntTag {
+                ty: Ident::new("u32", Span::call_site()),
+                max: u32::MAX.into(),
+            },
+            _ => IntTag {
+                ty: Ident::new("usize", Span::call_site()),
+                max: u64::MAX,
+            },
+        }
+    }
+}
+
+/// Options collected from the `#[trait_decode(...)]` attributes of a field.
+#[derive(Default)]
+pub(crate) struct FieldAttrs {
+    /// `skip`: not written to the stream, decoded as `Default::default()`.
+    pub skip: bool,
+    /// `default = expr`: skipped, decoded as `expr`, which may refer to earlier fields.
+    pub default: Option<Expr>,
+    /// `default_with = path`: skipped, decoded as `path(&__Context)`.
+    pub default_with: Option<Path>,
+    /// `from_context = path`: skipped, decoded as `path(&__Context)`, and a generic context is
+    /// bounded by the trait of `Trait::method`.
+    pub from_context: Option<Path>,
+    /// `encode_with = path` (or `with = path`): encoded as `path::encode(&field, encoder)`.
+    pub encode_with: Option<Path>,
+    /// `decode_with = path` (or `with = path`): decoded as `path::decode::<__Context, _>(decoder)`.
+    pub decode_with: Option<Path>,
+    /// `lookup(...)`: encoded as a key, decoded by resolving the key in the context.
+    pub lookup: Option<Lookup>,
+    /// `context = |ctx| ...` or `context_via = path`: decoded with the context projected by the
+    /// closure or function, which gets `&__Context` and returns the field's context by value. The
+    /// decoder is borrowed mutably while the field is decoded, so the projection can only borrow
+    /// from a reference context, as in `context = |ctx| &ctx.names` with `context_type = &'a
+    /// Session`. With an owned context, it has to return an owned value, like a clone or an
+    /// `Arc`; a closure returning `&...` is rejected, and a borrowing `context_via` function
+    /// fails to compile with a lifetime error.
+    pub context: Option<ContextLens>,
+    /// `context_from = &sibling` or `context_from = sibling`: decoded with a reference to, or a
+    /// clone of, a field declared before it as its context. Fields are never reordered, so a later
+    /// sibling is an error rather than a change to the format.
+    pub context_from: Option<ContextFrom>,
+    /// `len = expr` or `len_with = path`: a collection written without its length prefix. The
+    /// context is not known when encoding, so `len_with` cannot be checked then: a collection of
+    /// another length than the decoding context gives is written anyway, which corrupts the
+    /// stream without an error. Prefer `len` with earlier fields where the format allows it.
+    pub len: Option<Length>,
+    /// `if = expr` or `if_with = path`: an `Option` that is only written when the condition holds.
+    /// The context is not known when encoding, so `if_with` cannot be checked then: a `Some`
+    /// value is written even if the condition will not hold when decoding, which corrupts the
+    /// stream without an error. Prefer `if` with earlier fields where the format allows it.
+    pub condition: Option<Condition>,
+    /// `register = path`: `fn(&mut __Context, &Field)`, called right after the field is decoded,
+    /// and a generic context is bounded by the trait of `Trait::method`.
+    pub register: Option<Path>,
+}
+
+/// The condition under which an `Option` field is present.
+pub(crate) enum Condition {
+    /// `if = expr`, which may refer to earlier fields and is checked against the value when
+    /// encoding.
+    Expr(Expr),
+    /// `if_with = path`, decoded as `path(&__Context)`. Encoding writes any `Some` value, even one
+    /// the decoding context will not expect.
+    With(Path),
+}
+
+/// Where the length of a collection written without a length prefix comes from.
+pub(crate) enum Length {
+    /// `len = expr`, which may refer to earlier fields and is checked when encoding.
+    Expr(Expr),
+    /// `len_with = path`, decoded as `path(&__Context)` and not checked when encoding, so a wrong
+    /// length is only noticed, if at all, by what is decoded after it.
+    With(Path),
+}
+
+pub(crate) struct ContextFrom {
+    pub sibling: Member,
+    pub by_ref: bool,
+}
+
+/// A projection of the context for a single field.
+pub(crate) enum ContextLens {
+    /// `context = expr`, usually a closure.
+    Closure(Expr),
+    /// `context_via = path`, which bounds a generic context like `from_context` does.
+    Via(Path),
+}
+
+/// A field that is written as a key and looked up in the context when decoding, as in
+/// `lookup(key = isize, encode = |p| p.id, resolve = ParticleListTrait::get_particle_list,
+/// by = get_particle_from_id)`.
+pub(crate) struct Lookup {
+    /// The type of the key on the wire, which must be `Clone + Debug` for the error message.
+    pub key: Type,
+    /// A function or non-capturing closure from `&Field` to the key.
+    pub encode: Expr,
+    /// A function from `&__Context` to the registry, the context itself if omitted.
+    pub resolve: Option<Path>,
+    /// The method of the registry that maps the key to an `Option<Field>`.
+    pub by: Ident,
+}
+
+impl Lookup {
+    fn parse(meta: &ParseNestedMeta) -> syn::Result<Self> {
+        let mut key = None;
+        let mut encode = None;
+        let mut resolve = None;
+        let mut by = None;
+        meta.parse_nested_meta(|meta| {
+            if meta.path.is_ident("key") {
+                set_once(&meta, &mut key, meta.value()?.parse()?)
+            } else if meta.path.is_ident("encode") {
+                set_once(&meta, &mut encode, meta.value()?.parse()?)
+            } else if meta.path.is_ident("resolve") {
+                set_once(&meta, &mut resolve, meta.value()?.parse()?)
+            } else if meta.path.is_ident("by") {
+                set_once(&meta, &mut by, meta.value()?.parse()?)
+            } else {
+                Err(meta.error(
+                    "unrecognized key for lookup, supported keys are `key`, `encode`, `resolve` and `by`",
+                ))
+            }
+        })?;
+        let missing = |name| meta.error(format!("missing `{name}` in lookup"));
+        Ok(Lookup {
+            key: key.ok_or_else(|| missing("key"))?,
+            encode: encode.ok_or_else(|| missing("encode"))?,
+            resolve,
+            by: by.ok_or_else(|| missing("by"))?,
+        })
+    }
+}
+
+fn set_once<T>(meta: &ParseNestedMeta, slot: &mut Option<T>, value: T) -> syn::Result<()> {
+    if slot.is_some() {
+        return Err(meta.error("duplicate key in #[trait_decode]"));
+    }
+    *slot = Some(value);
+    Ok(())
+}
+
+impl FieldAttrs {
+    pub fn parse(attrs: &[Attribute]) -> syn::Result<Self> {
+        let mut out = FieldAttrs::default();
+        for attr in attrs.iter().filter(|a| a.path().is_ident("trait_decode")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("skip") {
+                    out.skip = true;
+                    Ok(())
+                } else if meta.path.is_ident("default") {
+                    if out.has_default() {
+                        return Err(meta.error("cannot specify more than one of `default`, `default_with` and `from_context` in #[trait_decode]"));
+                    }
+                    out.default = Some(meta.value()?.parse()?);
+                    Ok(())
+                } else if meta.path.is_ident("default_with") {
+                    if out.has_default() {
+                        return Err(meta.error("cannot specify more than one of `default`, `default_with` and `from_context` in #[trait_decode]"));
+                    }
+                    out.default_with = Some(meta.value()?.parse()?);
+                    Ok(())
+                } else if meta.path.is_ident("from_context") {
+                    if out.has_default() {
+                        return Err(meta.error("cannot specify more than one of `default`, `default_with` and `from_context` in #[trait_decode]"));
+                    }
+                    out.from_context = Some(meta.value()?.parse()?);
+                    Ok(())
+                } else if meta.path.is_ident("with") {
+                    let path: Path = meta.value()?.parse()?;
+                    set_hook(&meta, &mut out.encode_with, &path)?;
+                    set_hook(&meta, &mut out.decode_with, &path)
+                } else if meta.path.is_ident("encode_with") {
+                    let path: Path = meta.value()?.parse()?;
+                    set_hook(&meta, &mut out.encode_with, &path)
+                } else if meta.path.is_ident("decode_with") {
+                    let path: Path = meta.value()?.parse()?;
+                    set_hook(&meta, &mut out.decode_with, &path)
+                } else if meta.path.is_ident("context") || meta.path.is_ident("context_via") {
+                    if out.context.is_some() {
+                        return Err(meta.error("cannot specify more than one of `context` and `context_via` in #[trait_decode]"));
+                    }
+                    let value = meta.value()?;
+                    out.context = Some(if meta.path.is_ident("context") {
+                        ContextLens::Closure(value.parse()?)
+                    } else {
+                        ContextLens::Via(value.parse()?)
+                    });
+                    Ok(())
+                } else if meta.path.is_ident("context_from") {
+                    if out.context_from.is_some() {
+                        return Err(meta.error("duplicate `context_from` in #[trait_decode]"));
+                    }
+                    let value = meta.value()?;
+                    let by_ref = value.parse::<Option<Token![&]>>()?.is_some();
+                    out.context_from = Some(ContextFrom {
+                        sibling: value.parse()?,
+                        by_ref,
+                    });
+                    Ok(())
+                } else if meta.path.is_ident("len") || meta.path.is_ident("len_with") {
+                    if out.len.is_some() {
+                        return Err(meta.error("cannot specify more than one of `len` and `len_with` in #[trait_decode]"));
+                    }
+                    let value = meta.value()?;
+                    out.len = Some(if meta.path.is_ident("len") {
+                        Length::Expr(value.parse()?)
+                    } else {
+                        Length::With(value.parse()?)
+                    });
+                    Ok(())
+                } else if meta.path.is_ident("if") || meta.path.is_ident("if_with") {
+                    if out.condition.is_some() {
+                        return Err(meta.error("cannot specify more than one of `if` and `if_with` in #[trait_decode]"));
+                    }
+                    let value = meta.value()?;
+                    out.condition = Some(if meta.path.is_ident("if") {
+                        Condition::Expr(value.parse()?)
+                    } else {
+                        Condition::With(value.parse()?)
+                    });
+                    Ok(())
+                } else if meta.path.is_ident("register") {
+                    set_once(&meta, &mut out.register, meta.value()?.parse()?)
+                } else if meta.path.is_ident("lookup") {
+                    if out.lookup.is_some() {
+                        return Err(meta.error("duplicate `lookup` in #[trait_decode]"));
+                    }
+                    out.lookup = Some(Lookup::parse(&meta)?);
+                    Ok(())
+                } else {
+                    Err(meta.error("unrecognized key for #[trait_decode] field attribute, supported keys are `skip`, `default`, `default_with`, `from_context`, `with`, `encode_with`, `decode_with`, `lookup`, `context`, `context_via`, `context_from`, `len`, `len_with`, `if`, `if_with` and `register`"))
+                }
+            })?;
+        }
+        let has_hooks = out.encode_with.is_some() || out.decode_with.is_some();
+        let codecs = [
+            out.is_skipped(),
+            has_hooks,
+            out.lookup.is_some(),
+            out.context.is_some(),
+            out.context_from.is_some(),
+            out.len.is_some(),
+            out.condition.is_some(),
+        ];
+        if codecs.into_iter().filter(|&set| set).count() > 1 {
+            let attr = attrs
+                .iter()
+                .find(|a| a.path().is_ident("trait_decode"))
+                .expect("field attributes were parsed from a #[trait_decode]");
+            return Err(syn::Error::new_spanned(
+                attr,
+                "only one of skipping, codec hooks, `lookup`, a context projection, `len` and `if` can be used on a field in #[trait_decode]",
+            ));
+        }
+        Ok(out)
+    }
+
+    /// Whether the field is left out of the wire format.
+    pub fn is_skipped(&self) -> bool {
+        self.skip || self.has_default()
+    }
+
+    /// Whether the field is decoded from something other than `Default::default()` when skipped.
+    fn has_default(&self) -> bool {
+        self.default.is_some() || self.default_with.is_some() || self.from_context.is_some()
+    }
+}
+
+/// Sets an `encode_with` or `decode_with` hook, which `with` sets both of.
+fn set_hook(meta: &ParseNestedMeta, slot: &mut Option<Path>, path: &Path) -> syn::Result<()> {
+    if slot.is_some() {
+        return Err(meta.error("duplicate codec hook in #[trait_decode]"));
+    }
+    *slot = Some(path.clone());
+    Ok(())
+}
+
+/// Options collected from the `#[trait_decode(...)]` attributes of an enum variant.
+#[derive(Default)]
+pub(crate) struct VariantAttrs {
+    /// `skip`: the variant cannot be encoded and takes no discriminant.
+    pub skip: bool,
+    /// `index = N`: the discriminant the variant is written with, overriding `Variant = N`.
+    pub index: Option<u32>,
+    /// `rename = "..."`: the name the variant is written with under `tag = "name"`.
+    pub rename: Option<LitStr>,
+    /// `alias = "..."`: older names that are still accepted when decoding, may be repeated.
+    pub aliases: Vec<LitStr>,
+    /// `unknown`: holds the tag and the payload of the variants that are not known when decoding,
+    /// as in `Unknown { tag: u32, bytes: Vec<u8> }` with `tag = u32`, and writes them back as
+    /// they were. The tag field has the type of the tag, a `String` with `tag = "name"`.
+    pub unknown: bool,
+}
+
+impl VariantAttrs {
+    pub fn parse(attrs: &[Attribute]) -> syn::Result<Self> {
+        let mut out = VariantAttrs::default();
+        for attr in attrs.iter().filter(|a| a.path().is_ident("trait_decode")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("skip") {
+                    out.skip = true;
+                    Ok(())
+                } else if meta.path.is_ident("index") {
+                    let index: LitInt = meta.value()?.parse()?;
+                    set_once(&meta, &mut out.index, index.base10_parse()?)
+                } else if meta.path.is_ident("rename") {
+                    set_once(&meta, &mut out.rename, meta.value()?.parse()?)
+                } else if meta.path.is_ident("unknown") {
+                    out.unknown = true;
+                    Ok(())
+                } else if meta.path.is_ident("alias") {
+                    out.aliases.push(meta.value()?.parse()?);
+                    Ok(())
+                } else {
+                    Err(meta.error("unrecognized key for #[trait_decode] variant attribute, supported keys are `skip`, `index`, `rename`, `alias` and `unknown`"))
+                }
+            })?;
+        }
+        Ok(out)
+    }
+}
@@ -0,0 +1,221 @@
+use quote::ToTokens;
+use syn::{
+    GenericParam, Generics, Ident, Path, Type, TypePath,
+    visit::{self, Visit},
+};
+
+/// Returns the type parameters of `generics` that are encoded as values by the given field types,
+/// in declaration order.
+///
+/// A parameter that only appears inside `PhantomData<..>`, or only as the root of an associated
+/// type projection such as `F::Element`, is never encoded itself and therefore needs no bound.
+pub(crate) fn used_type_params<'a, 'ty>(
+    generics: &'a Generics,
+    field_types: impl IntoIterator<Item = &'ty Type>,
+) -> Vec<&'a Ident> {
+    let params: Vec<&Ident> = type_params(generics).collect();
+
+    let mut visitor = ParamUsage {
+        params: &params,
+        used: vec![false; params.len()],
+    };
+    for ty in field_types {
+        visitor.visit_type(ty);
+    }
+
+    params
+        .iter()
+        .zip(visitor.used)
+        .filter_map(|(param, used)| used.then_some(*param))
+        .collect()
+}
+
+struct ParamUsage<'p, 'a> {
+    params: &'p [&'a Ident],
+    used: Vec<bool>,
+}
+
+impl<'ast> Visit<'ast> for ParamUsage<'_, '_> {
+    fn visit_type_path(&mut self, type_path: &'ast TypePath) {
+        let path = &type_path.path;
+
+        if is_phantom_data(path) {
+            return;
+        }
+
+        if type_path.qself.is_some() {
+            // `<F as Trait>::Assoc` encodes the projection, not `F`
+            self.visit_path(path);
+            return;
+        }
+
+        if path.leading_colon.is_none() && path.segments.len() == 1 {
+            let ident = &path.segments[0].ident;
+            if let Some(position) = self.params.iter().position(|param| *param == ident) {
+                self.used[position] = true;
+            }
+        }
+
+        visit::visit_type_path(self, type_path);
+    }
+}
+
+/// Collects the associated type projections rooted at one of the type parameters of `generics`,
+/// like `F::Element` or `<F as Ring>::Element`, wherever they occur in the field types (e.g. in
+/// `Option<Vec<F::Element>>`, `(F::Element, E)` or `[F::Element; N]`). These need their own bound
+/// as the trait of `F` does not provide one.
+///
+/// Every projection is returned once, in order of first occurrence.
+pub(crate) fn associated_types<'ty>(
+    generics: &Generics,
+    field_types: impl IntoIterator<Item = &'ty Type>,
+) -> Vec<Type> {
+    let params: Vec<&Ident> = type_params(generics).collect();
+
+    let mut visitor = Projections {
+        params: &params,
+        found: Vec::new(),
+    };
+    for ty in field_types {
+        visitor.visit_type(ty);
+    }
+    visitor.found
+}
+
+/// Returns the field types that do not mention any type parameter of `generics`, each once.
+pub(crate) fn concrete_types<'ty>(
+    generics: &Generics,
+    field_types: impl IntoIterator<Item = &'ty Type>,
+) -> Vec<&'ty Type> {
+    let params: Vec<&Ident> = type_params(generics).collect();
+
+    let mut concrete: Vec<&Type> = Vec::new();
+    for ty in field_types {
+        if mentions(&params, ty) {
+            continue;
+        }
+
+        let tokens = ty.to_token_stream().to_string();
+        if !concrete
+            .iter()
+            .any(|seen| seen.to_token_stream().to_string() == tokens)
+        {
+            concrete.push(ty);
+        }
+    }
+    concrete
+}
+
+/// Whether `ty` mentions any type parameter of `generics`, including inside `PhantomData`.
+pub(crate) fn mentions_type_params(generics: &Generics, ty: &Type) -> bool {
+    let params: Vec<&Ident> = type_params(generics).collect();
+    mentions(&params, ty)
+}
+
+fn mentions(params: &[&Ident], ty: &Type) -> bool {
+    let mut visitor = Mentions {
+        params,
+        found: false,
+    };
+    visitor.visit_type(ty);
+    visitor.found
+}
+
+/// The trait of an accessor path like `ParticleListTrait::get_particle_list`, used to bound a
+/// generic context that the accessor is called on. A single-segment path is a free function and
+/// implies no bound.
+pub(crate) fn accessor_trait(accessor: &Path) -> Option<Path> {
+    if accessor.segments.len() < 2 {
+        return None;
+    }
+    let mut bound = accessor.clone();
+    bound.segments.pop();
+    bound.segments.pop_punct();
+    Some(bound)
+}
+
+fn type_params(generics: &Generics) -> impl Iterator<Item = &Ident> {
+    generics.params.iter().filter_map(|param| match param {
+        GenericParam::Type(type_param) => Some(&type_param.ident),
+        _ => None,
+    })
+}
+
+fn is_phantom_data(path: &Path) -> bool {
+    path.segments
+        .last()
+        .is_some_and(|segment| segment.ident == "PhantomData")
+}
+
+struct Projections<'p, 'a> {
+    params: &'p [&'a Ident],
+    found: Vec<Type>,
+}
+
+impl Projections<'_, '_> {
+    /// Whether `type_path` is a type parameter or a projection out of one.
+    fn is_rooted_at_param(&self, type_path: &TypePath) -> bool {
+        match &type_path.qself {
+            Some(qself) => match &*qself.ty {
+                Type::Path(inner) => self.is_rooted_at_param(inner),
+                _ => false,
+            },
+            None => {
+                type_path.path.leading_colon.is_none()
+                    && type_path
+                        .path
+                        .segments
+                        .first()
+                        .is_some_and(|segment| self.params.contains(&&segment.ident))
+            }
+        }
+    }
+
+    fn push(&mut self, ty: Type) {
+        let tokens = ty.to_token_stream().to_string();
+        if !self
+            .found
+            .iter()
+            .any(|found| found.to_token_stream().to_string() == tokens)
+        {
+            self.found.push(ty);
+        }
+    }
+}
+
+impl<'ast> Visit<'ast> for Projections<'_, '_> {
+    fn visit_type_path(&mut self, type_path: &'ast TypePath) {
+        if is_phantom_data(&type_path.path) {
+            return;
+        }
+
+        let is_projection = type_path.qself.is_some() || type_path.path.segments.len() > 1;
+        if is_projection && self.is_rooted_at_param(type_path) {
+            self.push(Type::Path(type_path.clone()));
+        }
+
+        // Generic arguments of the path may contain further projections, as in
+        // `Vec<F::Element>` or `F::Polynomial<G::Element>`
+        self.visit_path(&type_path.path);
+    }
+}
+
+/// Whether any type parameter is mentioned at all, including inside `PhantomData`.
+struct Mentions<'p, 'a> {
+    params: &'p [&'a Ident],
+    found: bool,
+}
+
+impl<'ast> Visit<'ast> for Mentions<'_, '_> {
+    fn visit_path(&mut self, path: &'ast Path) {
+        if path.leading_colon.is_none()
+            && path
+                .segments
+                .first()
+                .is_some_and(|segment| self.params.contains(&&segment.ident))
+        {
+            self.found = true;
+        }
+        visit::visit_path(self, path);
+    }
+}
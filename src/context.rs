@@ -0,0 +1,187 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{
+    BoundLifetimes, DeriveInput, GenericParam, Generics, Ident, Lifetime, LifetimeParam,
+    ParenthesizedGenericArguments, Token, Type, TypeBareFn, TypeParam, TypeReference, WhereClause,
+    WherePredicate,
+    parse::{Parse, ParseStream, discouraged::Speculative},
+    visit_mut::{self, VisitMut},
+};
+
+use crate::attr::ContainerAttrs;
+
+/// The generics, where-clause and context type of a single Decode or BorrowDecode impl.
+pub(crate) struct ImplContext {
+    pub generics: Generics,
+    pub where_clause: WhereClause,
+    pub context_type: TokenStream,
+}
+
+/// Returns the contexts to generate Decode and BorrowDecode impls for.
+///
+/// That is a single impl for a fresh generic `__Context` (bounded by `trait = ...`) or for the
+/// struct's own `context_param`, or one impl per type listed in `context_type = [...]`.
+pub(crate) fn impl_contexts(input: &DeriveInput, attrs: &ContainerAttrs) -> Vec<ImplContext> {
+    let mut struct_generics = input.generics.clone();
+    let where_clause = struct_generics.make_where_clause().clone();
+
+    if !attrs.context_types.is_empty() {
+        return attrs
+            .context_types
+            .iter()
+            .map(|concrete| {
+                let mut generics = input.generics.clone();
+                let concrete_type = concrete.add_to_impl(&mut generics);
+                ImplContext {
+                    generics,
+                    where_clause: where_clause.clone(),
+                    context_type: quote! { #concrete_type },
+                }
+            })
+            .collect();
+    }
+
+    let mut generics = input.generics.clone();
+    let context_type = match &attrs.context_param {
+        // The struct's own parameter is already part of the impl generics
+        Some(param) => quote! { #param },
+        None => {
+            let context_ident = Ident::new("__Context", proc_macro2::Span::call_site());
+            generics
+                .params
+                .push(GenericParam::Type(TypeParam::from(context_ident.clone())));
+            quote! { #context_ident }
+        }
+    };
+
+    // Without `trait`, a generic __Context is only constrained by the field requirements,
+    // e.g., `usize: Decode<__Context>` implies nothing while `Particle: Decode<__Context>` needs a
+    // `ParticleListTrait` context.
+    let mut where_clause = where_clause;
+    if !attrs.trait_bounds.is_empty() {
+        let trait_bounds = &attrs.trait_bounds;
+        let pred: WherePredicate = syn::parse_quote! { #context_type: #(#trait_bounds)+* };
+        where_clause.predicates.push(pred);
+    }
+
+    vec![ImplContext {
+        generics,
+        where_clause,
+        context_type,
+    }]
+}
+
+/// A concrete context given by `context_type = ...`.
+///
+/// Any type is accepted, including references and tuples such as `&'a ParticleList` or
+/// `(&'a ParticleList, &'a FishList)`. Lifetimes that are not declared on the struct are added to
+/// the generated impl, and elided ones (`&ParticleList`, `'_`) get a fresh impl lifetime. Type
+/// parameters that are not declared on the struct can be introduced with a leading generics list,
+/// as in `context_type = <C: ParticleListTrait> &'a C`.
+pub(crate) struct ContextType {
+    pub generics: Generics,
+    pub ty: Type,
+}
+
+impl Parse for ContextType {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        // `<C> Registry<C>` introduces `C`, while `<C as Trait>::Context` is a qualified path
+        if input.peek(Token![<]) {
+            let fork = input.fork();
+            if let Ok(generics) = fork.parse::<Generics>()
+                && let Ok(ty) = fork.parse::<Type>()
+            {
+                input.advance_to(&fork);
+                return Ok(ContextType { generics, ty });
+            }
+        }
+
+        Ok(ContextType {
+            generics: Generics::default(),
+            ty: input.parse()?,
+        })
+    }
+}
+
+impl ContextType {
+    /// Adds the generics this context introduces to the impl generics and returns the context
+    /// type to use in the impl, with all elided lifetimes named.
+    pub fn add_to_impl(&self, generics: &mut Generics) -> Type {
+        let mut ty = self.ty.clone();
+        let mut lifetimes = ContextLifetimes::default();
+        lifetimes.visit_type_mut(&mut ty);
+
+        let declared: Vec<Lifetime> = generics
+            .lifetimes()
+            .chain(self.generics.lifetimes())
+            .map(|param| param.lifetime.clone())
+            .collect();
+        for lifetime in lifetimes.found {
+            if lifetime.ident == "static"
+                || declared.contains(&lifetime)
+                || lifetimes.bound.contains(&lifetime)
+            {
+                continue;
+            }
+            generics
+                .params
+                .push(GenericParam::Lifetime(LifetimeParam::new(lifetime)));
+        }
+
+        generics.params.extend(self.generics.params.iter().cloned());
+
+        ty
+    }
+}
+
+/// Collects the lifetimes of a context type, naming the elided ones on the way.
+#[derive(Default)]
+struct ContextLifetimes {
+    found: Vec<Lifetime>,
+    /// Lifetimes bound by a `for<'a>` inside the type, which must not become impl parameters.
+    bound: Vec<Lifetime>,
+    elided: usize,
+}
+
+impl ContextLifetimes {
+    fn fresh(&mut self) -> Lifetime {
+        let lifetime = Lifetime::new(
+            &format!("'__context{}", self.elided),
+            proc_macro2::Span::call_site(),
+        );
+        self.elided += 1;
+        lifetime
+    }
+}
+
+impl VisitMut for ContextLifetimes {
+    fn visit_type_reference_mut(&mut self, reference: &mut TypeReference) {
+        if reference.lifetime.is_none() {
+            reference.lifetime = Some(self.fresh());
+        }
+        visit_mut::visit_type_reference_mut(self, reference);
+    }
+
+    fn visit_lifetime_mut(&mut self, lifetime: &mut Lifetime) {
+        if lifetime.ident == "_" {
+            *lifetime = self.fresh();
+        }
+        if !self.found.contains(lifetime) {
+            self.found.push(lifetime.clone());
+        }
+    }
+
+    // Elided lifetimes in `fn(&T)` and `Fn(&T)` are late-bound and stay elided
+    fn visit_type_bare_fn_mut(&mut self, _: &mut TypeBareFn) {}
+
+    fn visit_parenthesized_generic_arguments_mut(&mut self, _: &mut ParenthesizedGenericArguments) {
+    }
+
+    fn visit_bound_lifetimes_mut(&mut self, bound_lifetimes: &mut BoundLifetimes) {
+        for param in &bound_lifetimes.lifetimes {
+            if let GenericParam::Lifetime(param) = param {
+                self.bound.push(param.lifetime.clone());
+            }
+        }
+    }
+}
@@ -0,0 +1,432 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{Expr, Fields, Ident, Path};
+
+use crate::attr::{Condition, ContextLens, Length};
+use crate::model::{Body, Collection, Field, FieldList, Input, Variant};
+
+/// Generates the body of `Decode::decode`.
+pub(crate) fn decode_body(input: &Input) -> TokenStream {
+    let attrs = &input.attrs;
+    let krate = &attrs.krate;
+    let decode_value = decode_value(input);
+    if attrs.validate.is_none() && attrs.finish.is_none() {
+        return decode_value;
+    }
+
+    let validate = attrs.validate.as_ref().map(|validate| {
+        let prefix = format!("invalid `{}`: ", input.ast.ident);
+        quote! {
+            if let ::core::result::Result::Err(message) =
+                #validate(&value, &*#krate::de::Decoder::context(__decoder))
+            {
+                return ::core::result::Result::Err(#krate::error::DecodeError::OtherString(
+                    ::std::format!("{}{}", #prefix, message),
+                ));
+            }
+        }
+    });
+    let finish = attrs.finish.as_ref().map(|finish| {
+        quote! { #finish(&mut value, #krate::de::Decoder::context(__decoder)); }
+    });
+    quote! {
+        #[allow(unused_mut)]
+        let mut value: Self = { #decode_value }?;
+        #validate
+        #finish
+        ::core::result::Result::Ok(value)
+    }
+}
+
+/// Decodes the value itself, evaluating to a `Result<Self, DecodeError>`.
+fn decode_value(input: &Input) -> TokenStream {
+    let krate = &input.attrs.krate;
+    let struct_name = &input.ast.ident;
+
+    if let Some(by_key) = &input.attrs.by_key {
+        let key_field = input
+            .key_field()
+            .expect("the `by_key` field was checked when parsing");
+        let key_ty = key_field.ty;
+        let key = decode_field(input, key_field, &struct_name.to_string(), &[]);
+        let find = find_in_context(
+            input,
+            by_key.registry.as_ref(),
+            &by_key.find,
+            &format!("cannot resolve `{struct_name}`"),
+        );
+        return quote! {
+            let key: #key_ty = #key;
+            #find
+        };
+    }
+
+    match &input.body {
+        Body::Struct(fields) => {
+            decode_fields(input, fields, quote! { Self }, struct_name.to_string())
+        }
+        Body::Enum(variants) => {
+            // Skipped variants take no discriminant and are never decoded, and the `unknown`
+            // variant takes whatever discriminant is left
+            let decoded_variants: Vec<_> = variants
+                .iter()
+                .filter(|variant| !variant.attrs.skip && !variant.attrs.unknown)
+                .collect();
+            let unknown = variants.iter().find(|variant| variant.attrs.unknown);
+            if decoded_variants.is_empty() && unknown.is_none() {
+                return quote! {
+                    ::core::result::Result::Err(#krate::error::DecodeError::EmptyEnum {
+                        type_name: ::core::stringify!(#struct_name),
+                    })
+                };
+            }
+
+            let variants = decoded_variants.iter().map(|variant| {
+                let variant_ident = variant.ident;
+                let mut construct = decode_fields(
+                    input,
+                    &variant.fields,
+                    quote! { Self::#variant_ident },
+                    format!("{struct_name}::{variant_ident}"),
+                );
+                if input.attrs.length_prefixed {
+                    construct = from_payload(input, construct);
+                }
+                if input.attrs.by_name() {
+                    let name = &variant.name;
+                    let aliases = &variant.attrs.aliases;
+                    quote! { #name #(| #aliases)* => { #construct } }
+                } else {
+                    let index = proc_macro2::Literal::u32_unsuffixed(variant.index);
+                    quote! { #index => { #construct } }
+                }
+            });
+
+            let (tag, fallback) = if input.attrs.by_name() {
+                let names: Vec<_> = decoded_variants
+                    .iter()
+                    .map(|variant| format!("`{}`", variant.name))
+                    .collect();
+                let message = format!(
+                    "unknown variant {{:?}} of `{struct_name}`, expected one of {}",
+                    names.join(", ")
+                );
+                let fallback = quote! {
+                    _other => ::core::result::Result::Err(
+                        #krate::error::DecodeError::OtherString(::std::format!(#message, _other)),
+                    ),
+                };
+                (quote! { ::std::string::String }, fallback)
+            } else {
+                let tag = input.attrs.int_tag().ty;
+                let allowed: Vec<_> = decoded_variants
+                    .iter()
+                    .map(|variant| variant.index)
+                    .collect();
+                // `UnexpectedVariant` only holds a `u32`, which a wider tag may not fit in
+                let message = format!(
+                    "unexpected variant index {{}} of `{struct_name}`, expected one of {:?}",
+                    allowed
+                );
+                let fallback = quote! {
+                    _other => ::core::result::Result::Err(match <u32 as ::core::convert::TryFrom<#tag>>::try_from(_other) {
+                        ::core::result::Result::Ok(found) => {
+                            #krate::error::DecodeError::UnexpectedVariant {
+                                type_name: ::core::stringify!(#struct_name),
+                                found,
+                                allowed: &#krate::error::AllowedEnumVariants::Allowed(&[#(#allowed),*]),
+                            }
+                        }
+                        ::core::result::Result::Err(_) => {
+                            #krate::error::DecodeError::OtherString(::std::format!(#message, _other))
+                        }
+                    }),
+                };
+                (quote! { #tag }, fallback)
+            };
+            let fallback = match unknown {
+                Some(unknown) => unknown_arm(input, unknown),
+                None => fallback,
+            };
+            let scrutinee = if input.attrs.by_name() {
+                quote! { discriminant.as_str() }
+            } else {
+                quote! { discriminant }
+            };
+            quote! {
+                let discriminant: #tag = #krate::Decode::decode(__decoder)?;
+                match #scrutinee {
+                    #(#variants)*
+                    #fallback
+                }
+            }
+        }
+    }
+}
+
+/// Decodes a known variant of a `length_prefixed` enum with `construct` from its payload alone,
+/// so that a payload that is too short is an error and any bytes a newer version appends are
+/// skipped. The payload gets its own decoder, with a clone of the context that is swapped back
+/// once the variant is decoded, to keep what `register` and `finish` change.
+fn from_payload(input: &Input, construct: TokenStream) -> TokenStream {
+    let krate = &input.attrs.krate;
+    let read_payload = read_payload(input);
+    quote! {
+        #read_payload
+        let mut payload_decoder = #krate::de::DecoderImpl::new(
+            #krate::de::read::SliceReader::new(&bytes),
+            *#krate::de::Decoder::config(__decoder),
+            ::core::clone::Clone::clone(#krate::de::Decoder::context(__decoder)),
+        );
+        let value = {
+            let __decoder = &mut payload_decoder;
+            #construct
+        }?;
+        ::core::mem::swap(
+            #krate::de::Decoder::context(__decoder),
+            #krate::de::Decoder::context(&mut payload_decoder),
+        );
+        ::core::result::Result::Ok(value)
+    }
+}
+
+/// Reads the length prefixed payload of a variant of a `length_prefixed` enum into `bytes`.
+fn read_payload(input: &Input) -> TokenStream {
+    let krate = &input.attrs.krate;
+    quote! {
+        let len: u64 = #krate::Decode::decode(__decoder)?;
+        let len = usize::try_from(len)
+            .map_err(|_| #krate::error::DecodeError::OutsideUsizeRange(len))?;
+        #krate::de::Decoder::claim_container_read::<u8>(__decoder, len)?;
+        let mut bytes = ::std::vec![0u8; len];
+        #krate::de::read::Reader::read(#krate::de::Decoder::reader(__decoder), &mut bytes)?;
+    }
+}
+
+/// The match arm that keeps the discriminant and the payload of a variant that is not known in
+/// the `unknown` variant.
+fn unknown_arm(input: &Input, unknown: &Variant) -> TokenStream {
+    let variant_ident = unknown.ident;
+    let [tag, bytes] = [0, 1].map(|i| &unknown.fields.fields[i].member);
+    let read_payload = read_payload(input);
+    quote! {
+        _ => {
+            #read_payload
+            ::core::result::Result::Ok(Self::#variant_ident {
+                #tag: discriminant,
+                #bytes: bytes,
+            })
+        }
+    }
+}
+
+/// Decodes the fields in declaration order into their locals, so that later fields can refer to
+/// earlier ones, registering those with `register` in the context, and then builds the value with
+/// `path`. `name` is the struct or variant as it appears in error messages.
+fn decode_fields(
+    input: &Input,
+    fields: &FieldList,
+    path: TokenStream,
+    name: String,
+) -> TokenStream {
+    let krate = &input.attrs.krate;
+    let mut decoded = Vec::new();
+    let decode_fields: Vec<_> = fields
+        .fields
+        .iter()
+        .map(|f| {
+            let local = &f.local;
+            let ty = f.ty;
+            let value = decode_field(input, f, &name, &decoded);
+            decoded.push(f);
+            let register = f.attrs.register.as_ref().map(|register| {
+                quote! { #register(#krate::de::Decoder::context(__decoder), &#local); }
+            });
+            // `_phantom` is decoded into `__field__phantom`
+            quote! {
+                #[allow(non_snake_case)]
+                let #local: #ty = #value;
+                #register
+            }
+        })
+        .collect();
+
+    let construct = match fields.style {
+        Fields::Named(_) => {
+            let members = fields.fields.iter().map(|f| {
+                let member = &f.member;
+                let local = &f.local;
+                quote! { #member: #local }
+            });
+            quote! { #path { #(#members),* } }
+        }
+        Fields::Unnamed(_) => {
+            let locals = fields.fields.iter().map(|f| &f.local);
+            quote! { #path(#(#locals),*) }
+        }
+        Fields::Unit => path,
+    };
+
+    quote! {
+        #(#decode_fields)*
+        ::core::result::Result::Ok(#construct)
+    }
+}
+
+/// The expression a single field decodes to. `name` is the struct or variant the field is in and
+/// `decoded` are the fields decoded before it.
+fn decode_field(input: &Input, field: &Field, name: &str, decoded: &[&Field]) -> TokenStream {
+    let krate = &input.attrs.krate;
+    let attrs = &field.attrs;
+
+    if let Some(default) = &attrs.default {
+        with_earlier_fields(decoded, default)
+    } else if let Some(accessor) = attrs.default_with.as_ref().or(attrs.from_context.as_ref()) {
+        quote! { #accessor(&*#krate::de::Decoder::context(__decoder)) }
+    } else if attrs.skip {
+        quote! { ::core::default::Default::default() }
+    } else if let Some(decode_with) = &attrs.decode_with {
+        // The body is shared by every impl, so the context type is taken from the decoder, which
+        // may be the one of a `length_prefixed` payload
+        quote! { #decode_with::decode::<<D as #krate::de::Decoder>::Context, _>(__decoder)? }
+    } else if let Some(lookup) = &attrs.lookup {
+        let key = &lookup.key;
+        let message = format!("cannot resolve `{name}.{}`", field.name());
+        let find = find_in_context(input, lookup.resolve.as_ref(), &lookup.by, &message);
+        quote! {{
+            let key: #key = #krate::Decode::decode(__decoder)?;
+            match #find {
+                ::core::result::Result::Ok(value) => value,
+                ::core::result::Result::Err(err) => return ::core::result::Result::Err(err),
+            }
+        }}
+    } else if let Some(lens) = &attrs.context {
+        let projection = match lens {
+            ContextLens::Closure(closure) => quote! { #closure },
+            ContextLens::Via(path) => quote! { #path },
+        };
+        // `with_context` borrows the decoder mutably, so the projected context cannot borrow
+        // from the decoder's own context
+        quote! {{
+            fn project<C: ?::core::marker::Sized, P>(
+                projection: impl ::core::ops::FnOnce(&C) -> P,
+                context: &C,
+            ) -> P {
+                projection(context)
+            }
+            let context = project(#projection, &*#krate::de::Decoder::context(__decoder));
+            #krate::Decode::decode(&mut #krate::de::Decoder::with_context(__decoder, context))?
+        }}
+    } else if let Some(sibling) = &field.sibling {
+        let local = &sibling.local;
+        let context = if sibling.by_ref {
+            quote! { &#local }
+        } else {
+            quote! { ::core::clone::Clone::clone(&#local) }
+        };
+        quote! {
+            #krate::Decode::decode(&mut #krate::de::Decoder::with_context(__decoder, #context))?
+        }
+    } else if let Some(collection) = &field.collection {
+        let len = match &attrs.len {
+            Some(Length::Expr(len)) => with_earlier_fields(decoded, len),
+            Some(Length::With(len_with)) => {
+                quote! { #len_with(&*#krate::de::Decoder::context(__decoder)) }
+            }
+            None => unreachable!("only `len` fields are collections"),
+        };
+        let read = match collection {
+            Collection::Vec(element) | Collection::BoxedSlice(element) => {
+                let into = match collection {
+                    Collection::BoxedSlice(_) => quote! { .into_boxed_slice() },
+                    _ => quote! {},
+                };
+                // Claims the bytes like bincode's own `Vec` impl, to respect a configured limit
+                quote! {
+                    #krate::de::Decoder::claim_container_read::<#element>(__decoder, len)?;
+                    let mut items = ::std::vec::Vec::with_capacity(len);
+                    for _ in 0..len {
+                        #krate::de::Decoder::unclaim_bytes_read(
+                            __decoder,
+                            ::core::mem::size_of::<#element>(),
+                        );
+                        items.push(#krate::Decode::decode(__decoder)?);
+                    }
+                    items #into
+                }
+            }
+            Collection::String => quote! {
+                #krate::de::Decoder::claim_container_read::<u8>(__decoder, len)?;
+                let mut bytes = ::std::vec![0u8; len];
+                #krate::de::read::Reader::read(#krate::de::Decoder::reader(__decoder), &mut bytes)?;
+                ::std::string::String::from_utf8(bytes).map_err(|e| {
+                    #krate::error::DecodeError::Utf8 {
+                        inner: e.utf8_error(),
+                    }
+                })?
+            },
+        };
+        quote! {{
+            let len: usize = #len;
+            #read
+        }}
+    } else if let Some(condition) = &attrs.condition {
+        let condition = match condition {
+            Condition::Expr(condition) => with_earlier_fields(decoded, condition),
+            Condition::With(condition) => {
+                quote! { #condition(&*#krate::de::Decoder::context(__decoder)) }
+            }
+        };
+        quote! {
+            if #condition {
+                ::core::option::Option::Some(#krate::Decode::decode(__decoder)?)
+            } else {
+                ::core::option::Option::None
+            }
+        }
+    } else {
+        quote! { #krate::Decode::decode(__decoder)? }
+    }
+}
+
+/// Evaluates `expr` with the earlier fields bound by name as references, as they are when
+/// encoding.
+fn with_earlier_fields(decoded: &[&Field], expr: &Expr) -> TokenStream {
+    let bindings = decoded.iter().map(|f| &f.binding);
+    let locals = decoded.iter().map(|f| &f.local);
+    quote! {{
+        #(
+            #[allow(unused_variables)]
+            let #bindings = &#locals;
+        )*
+        #expr
+    }}
+}
+
+/// Looks up the local `key` with `registry.by(key)`, where the registry is the context or
+/// `resolve(&context)`. Evaluates to a `Result` with a `DecodeError` naming the key if it is
+/// missing.
+fn find_in_context(
+    input: &Input,
+    resolve: Option<&Path>,
+    by: &Ident,
+    message: &str,
+) -> TokenStream {
+    let krate = &input.attrs.krate;
+    let context = quote! { &*#krate::de::Decoder::context(__decoder) };
+    let registry = match resolve {
+        Some(resolve) => quote! { #resolve(#context) },
+        None => context,
+    };
+    quote! {
+        match (#registry).#by(::core::clone::Clone::clone(&key)) {
+            ::core::option::Option::Some(value) => ::core::result::Result::Ok(value),
+            ::core::option::Option::None => ::core::result::Result::Err(
+                #krate::error::DecodeError::OtherString(::std::format!(
+                    ::core::concat!(#message, ": no entry for key {:?}"),
+                    key,
+                )),
+            ),
+        }
+    }
+}
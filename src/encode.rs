@@ -0,0 +1,325 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{Expr, Fields};
+
+use crate::attr::{Condition, Length};
+use crate::model::{Body, Collection, Field, FieldList, Input};
+
+/// Generates the body of `Encode::encode`.
+pub(crate) fn encode_body(input: &Input) -> TokenStream {
+    let encode_value = encode_value(input);
+    let Some(pre_encode) = &input.attrs.pre_encode else {
+        return encode_value;
+    };
+
+    let krate = &input.attrs.krate;
+    let prefix = format!("invalid `{}`: ", input.ast.ident);
+    quote! {
+        if let ::core::result::Result::Err(message) = #pre_encode(self) {
+            return ::core::result::Result::Err(#krate::error::EncodeError::OtherString(
+                ::std::format!("{}{}", #prefix, message),
+            ));
+        }
+        #encode_value
+    }
+}
+
+/// Encodes the value itself, evaluating to a `Result<(), EncodeError>`.
+fn encode_value(input: &Input) -> TokenStream {
+    let krate = &input.attrs.krate;
+    let type_name = &input.ast.ident;
+
+    if input.attrs.by_key.is_some() {
+        let key_field = input
+            .key_field()
+            .expect("the `by_key` field was checked when parsing");
+        let member = &key_field.member;
+        let encode_key = encode_field(
+            input,
+            key_field,
+            quote! { &self.#member },
+            &type_name.to_string(),
+            &[],
+        );
+        return quote! { #encode_key ::core::result::Result::Ok(()) };
+    }
+
+    match &input.body {
+        Body::Struct(fields) => {
+            let pattern = fields_pattern(quote! { Self }, fields, true);
+            let encode_fields = encode_fields(input, fields, &type_name.to_string());
+            quote! {
+                #[allow(non_snake_case)]
+                let #pattern = self;
+                #encode_fields
+                ::core::result::Result::Ok(())
+            }
+        }
+        Body::Enum(variants) => {
+            let tag = input.attrs.int_tag().ty;
+            let variant_arms = variants.iter().map(|variant| {
+                let variant_ident = variant.ident;
+                let pattern = fields_pattern(
+                    quote! { Self::#variant_ident },
+                    &variant.fields,
+                    !variant.attrs.skip,
+                );
+
+                if variant.attrs.skip {
+                    let message =
+                        format!("cannot encode skipped variant {type_name}::{variant_ident}");
+                    return quote! {
+                        #pattern => ::core::result::Result::Err(#krate::error::EncodeError::Other(
+                            #message,
+                        )),
+                    };
+                }
+
+                let tag_ty = if input.attrs.by_name() {
+                    quote! { str }
+                } else {
+                    quote! { #tag }
+                };
+                if variant.attrs.unknown {
+                    // Written back as it was read, the payload with its length prefix
+                    let [tag, bytes] = [0, 1].map(|i| &variant.fields.fields[i].local);
+                    return quote! {
+                        #[allow(non_snake_case)]
+                        #pattern => {
+                            <#tag_ty as #krate::Encode>::encode(#tag, __encoder)?;
+                            <[u8] as #krate::Encode>::encode(#bytes, __encoder)
+                        }
+                    };
+                }
+
+                let encode_tag = if input.attrs.by_name() {
+                    let name = &variant.name;
+                    quote! { <#tag_ty as #krate::Encode>::encode(#name, __encoder)?; }
+                } else {
+                    let discriminant = proc_macro2::Literal::u32_unsuffixed(variant.index);
+                    quote! { <#tag_ty as #krate::Encode>::encode(&#discriminant, __encoder)?; }
+                };
+                let mut encode_fields = encode_fields(
+                    input,
+                    &variant.fields,
+                    &format!("{type_name}::{variant_ident}"),
+                );
+                if input.attrs.length_prefixed {
+                    // The fields are encoded into a buffer with the same config, and written
+                    // like a `Vec<u8>`
+                    encode_fields = quote! {
+                        let mut payload_encoder = #krate::enc::EncoderImpl::new(
+                            PayloadWriter(::std::vec::Vec::new()),
+                            *#krate::enc::Encoder::config(__encoder),
+                        );
+                        {
+                            let __encoder = &mut payload_encoder;
+                            #encode_fields
+                        }
+                        let bytes = payload_encoder.into_writer().0;
+                        <[u8] as #krate::Encode>::encode(&bytes, __encoder)?;
+                    };
+                }
+                quote! {
+                    #[allow(non_snake_case)]
+                    #pattern => {
+                        #encode_tag
+                        #encode_fields
+                        ::core::result::Result::Ok(())
+                    }
+                }
+            });
+            // bincode's own `Vec` writer is private
+            let payload_writer = input.attrs.length_prefixed.then(|| {
+                quote! {
+                    struct PayloadWriter(::std::vec::Vec<u8>);
+
+                    impl #krate::enc::write::Writer for PayloadWriter {
+                        fn write(
+                            &mut self,
+                            bytes: &[u8],
+                        ) -> ::core::result::Result<(), #krate::error::EncodeError> {
+                            self.0.extend_from_slice(bytes);
+                            ::core::result::Result::Ok(())
+                        }
+                    }
+                }
+            });
+            quote! {
+                #payload_writer
+                match self {
+                    #(#variant_arms)*
+                }
+            }
+        }
+    }
+}
+
+/// Encodes the fields that are not skipped, from the locals `fields_pattern` binds.
+fn encode_fields(input: &Input, fields: &FieldList, name: &str) -> TokenStream {
+    let mut encoded = Vec::new();
+    let encode_fields: Vec<_> = fields
+        .fields
+        .iter()
+        .filter(|f| !f.attrs.is_skipped())
+        .map(|f| {
+            let local = &f.local;
+            let encode_field = encode_field(input, f, quote! { #local }, name, &encoded);
+            encoded.push(f);
+            encode_field
+        })
+        .collect();
+    quote! { #(#encode_fields)* }
+}
+
+/// Encodes the field behind the reference `value`, through its `encode_with` hook, as its `lookup`
+/// key, without a length prefix or only if its condition holds, if it has one. `name` is the
+/// struct or variant the field is in and `encoded` are the fields encoded before it.
+fn encode_field(
+    input: &Input,
+    field: &Field,
+    value: TokenStream,
+    name: &str,
+    encoded: &[&Field],
+) -> TokenStream {
+    let krate = &input.attrs.krate;
+    if let Some(hook) = &field.attrs.encode_with {
+        quote! { #hook::encode(#value, __encoder)?; }
+    } else if let Some(lookup) = &field.attrs.lookup {
+        let ty = field.ty;
+        let key = &lookup.key;
+        let encode_key = &lookup.encode;
+        // The fn pointer gives the closure its argument type
+        quote! {{
+            let encode_key: fn(&#ty) -> #key = #encode_key;
+            #krate::Encode::encode(&encode_key(#value), __encoder)?;
+        }}
+    } else if let Some(collection) = &field.collection {
+        // `len_with` needs the context, which is only known when decoding
+        let (len, check_len) = match &field.attrs.len {
+            Some(Length::Expr(len)) => {
+                let message = format!(
+                    "`{name}.{}` has length {{}} but `len` is {{}}",
+                    field.name()
+                );
+                let check_len = quote! {
+                    if value.len() != len {
+                        return ::core::result::Result::Err(#krate::error::EncodeError::OtherString(
+                            ::std::format!(#message, value.len(), len),
+                        ));
+                    }
+                };
+                let len = with_earlier_fields(encoded, len);
+                (quote! { let len: usize = #len; }, check_len)
+            }
+            _ => (quote! {}, quote! {}),
+        };
+        let write = match collection {
+            Collection::Vec(_) | Collection::BoxedSlice(_) => quote! {
+                for item in value.iter() {
+                    #krate::Encode::encode(item, __encoder)?;
+                }
+            },
+            Collection::String => quote! {
+                #krate::enc::write::Writer::write(
+                    #krate::enc::Encoder::writer(__encoder),
+                    value.as_bytes(),
+                )?;
+            },
+        };
+        quote! {{
+            let value = #value;
+            #len
+            #check_len
+            #write
+        }}
+    } else if let Some(condition) = &field.attrs.condition {
+        match condition {
+            Condition::Expr(condition) => {
+                let condition = with_earlier_fields(encoded, condition);
+                let present = format!(
+                    "`{name}.{}` is `Some` but its `if` condition does not hold",
+                    field.name()
+                );
+                let missing = format!(
+                    "`{name}.{}` is `None` but its `if` condition holds",
+                    field.name()
+                );
+                quote! {{
+                    let condition: bool = #condition;
+                    match (condition, #value) {
+                        (true, ::core::option::Option::Some(value)) => {
+                            #krate::Encode::encode(value, __encoder)?;
+                        }
+                        (false, ::core::option::Option::None) => {}
+                        (false, ::core::option::Option::Some(_)) => {
+                            return ::core::result::Result::Err(
+                                #krate::error::EncodeError::Other(#present),
+                            );
+                        }
+                        (true, ::core::option::Option::None) => {
+                            return ::core::result::Result::Err(
+                                #krate::error::EncodeError::Other(#missing),
+                            );
+                        }
+                    }
+                }}
+            }
+            // The context is only known when decoding, so the value decides
+            Condition::With(_) => quote! {
+                if let ::core::option::Option::Some(value) = #value {
+                    #krate::Encode::encode(value, __encoder)?;
+                }
+            },
+        }
+    } else {
+        quote! { #krate::Encode::encode(#value, __encoder)?; }
+    }
+}
+
+/// Evaluates `expr` with the earlier fields bound by name, as references like when decoding.
+fn with_earlier_fields(encoded: &[&Field], expr: &Expr) -> TokenStream {
+    let bindings = encoded.iter().map(|f| &f.binding);
+    let locals = encoded.iter().map(|f| &f.local);
+    quote! {{
+        #(
+            #[allow(unused_variables)]
+            let #bindings = #locals;
+        )*
+        #expr
+    }}
+}
+
+/// `Self { a: __field_a, b: _ }` or `Self::Variant(__field_0, _)` with `path`, binding every field
+/// that is encoded to its local if `bind` is set.
+fn fields_pattern(path: TokenStream, fields: &FieldList, bind: bool) -> TokenStream {
+    let FieldList { style, fields, .. } = fields;
+    let is_encoded = |f: &Field| bind && !f.attrs.is_skipped();
+
+    match style {
+        Fields::Named(_) => {
+            let field_pats = fields.iter().map(|f| {
+                let member = &f.member;
+                if is_encoded(f) {
+                    let local = &f.local;
+                    quote! { #member: #local }
+                } else {
+                    quote! { #member: _ }
+                }
+            });
+            quote! { #path { #(#field_pats),* } }
+        }
+        Fields::Unnamed(_) => {
+            let field_pats = fields.iter().map(|f| {
+                if is_encoded(f) {
+                    let local = &f.local;
+                    quote! { #local }
+                } else {
+                    quote! { _ }
+                }
+            });
+            quote! { #path ( #(#field_pats),* ) }
+        }
+        Fields::Unit => path,
+    }
+}
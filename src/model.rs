@@ -0,0 +1,653 @@
+use proc_macro2::Span;
+use quote::ToTokens;
+use syn::{
+    Data, DataEnum, DeriveInput, Expr, ExprLit, Fields, GenericArgument, Ident, Lit, LitStr,
+    Member, PathArguments, Stmt, Type, ext::IdentExt, spanned::Spanned,
+};
+
+use crate::attr::{Compat, ContainerAttrs, ContextLens, FieldAttrs, VariantAttrs};
+
+/// A derive input with all of its `#[trait_decode]` attributes parsed.
+pub(crate) struct Input<'a> {
+    pub ast: &'a DeriveInput,
+    pub attrs: ContainerAttrs,
+    pub body: Body<'a>,
+}
+
+pub(crate) enum Body<'a> {
+    Struct(FieldList<'a>),
+    Enum(Vec<Variant<'a>>),
+}
+
+pub(crate) struct Variant<'a> {
+    pub ident: &'a Ident,
+    pub attrs: VariantAttrs,
+    pub fields: FieldList<'a>,
+    /// The discriminant the variant is written with, unused if it is skipped or written by name.
+    pub index: u32,
+    /// The name the variant is written with under `tag = "name"`.
+    pub name: String,
+}
+
+/// The fields of a struct or variant, along with their style.
+pub(crate) struct FieldList<'a> {
+    pub style: &'a Fields,
+    pub fields: Vec<Field<'a>>,
+}
+
+pub(crate) struct Field<'a> {
+    /// `a` or `0`, to access the field on `self`.
+    pub member: Member,
+    /// The name `default`, `len` and `if` expressions refer to the field by: its own name, or
+    /// `field0`, `field1`, ... for tuple fields.
+    pub binding: Ident,
+    /// The local the field is decoded into, `__field_a` or `__field_0`, which cannot shadow the
+    /// generated code's own names.
+    pub local: Ident,
+    pub ty: &'a Type,
+    pub attrs: FieldAttrs,
+    /// The earlier sibling this field is decoded with as its context, from `context_from`.
+    pub sibling: Option<Sibling<'a>>,
+    /// The kind of collection a `len` or `len_with` field is.
+    pub collection: Option<Collection<'a>>,
+}
+
+/// A collection that can be written without a length prefix.
+pub(crate) enum Collection<'a> {
+    /// `Vec<T>`, with the element type.
+    Vec(&'a Type),
+    /// `Box<[T]>`, with the element type.
+    BoxedSlice(&'a Type),
+    String,
+}
+
+impl<'a> Collection<'a> {
+    fn of(ty: &'a Type) -> Option<Self> {
+        let (ident, argument) = last_segment(ty)?;
+        match (ident.to_string().as_str(), argument) {
+            ("Vec", Some(element)) => Some(Collection::Vec(element)),
+            ("Box", Some(Type::Slice(slice))) => Some(Collection::BoxedSlice(&slice.elem)),
+            ("String", None) => Some(Collection::String),
+            _ => None,
+        }
+    }
+}
+
+fn is_option(ty: &Type) -> bool {
+    matches!(last_segment(ty), Some((ident, Some(_))) if ident == "Option")
+}
+
+/// The name of a type like `Vec<T>` or `String`, and its type argument if it has exactly one.
+fn last_segment(ty: &Type) -> Option<(&Ident, Option<&Type>)> {
+    let Type::Path(path) = ty else {
+        return None;
+    };
+    if path.qself.is_some() {
+        return None;
+    }
+    let last = path.path.segments.last()?;
+    let argument = match &last.arguments {
+        PathArguments::None => None,
+        PathArguments::AngleBracketed(args) if args.args.len() == 1 => match &args.args[0] {
+            GenericArgument::Type(ty) => Some(ty),
+            _ => return None,
+        },
+        _ => return None,
+    };
+    Some((&last.ident, argument))
+}
+
+impl Field<'_> {
+    /// The field as it appears in error messages, `a` or `0`.
+    pub fn name(&self) -> String {
+        match &self.member {
+            Member::Named(ident) => ident.to_string(),
+            Member::Unnamed(index) => index.index.to_string(),
+        }
+    }
+}
+
+pub(crate) struct Sibling<'a> {
+    /// The local the sibling is decoded into.
+    pub local: Ident,
+    pub ty: &'a Type,
+    /// `context_from = &field` passes a reference, `context_from = field` a clone.
+    pub by_ref: bool,
+}
+
+impl<'a> Input<'a> {
+    pub fn parse(ast: &'a DeriveInput) -> syn::Result<Self> {
+        let attrs = ContainerAttrs::parse(ast)?;
+        let body = match &ast.data {
+            Data::Struct(data_struct) => Body::Struct(FieldList::parse(&data_struct.fields)?),
+            Data::Enum(data_enum) => Body::Enum(variants(data_enum, &attrs)?),
+            Data::Union(_) => {
+                return Err(syn::Error::new(
+                    Span::call_site(),
+                    "Unions are not supported by the bincode-trait-derive derives",
+                ));
+            }
+        };
+        if !matches!(body, Body::Enum(_)) {
+            if attrs.stable_indices {
+                return Err(syn::Error::new(
+                    Span::call_site(),
+                    "`stable_indices` is only supported on enums",
+                ));
+            }
+            if let Some(tag) = &attrs.tag {
+                return Err(syn::Error::new(
+                    tag.span(),
+                    "`tag` is only supported on enums",
+                ));
+            }
+            if attrs.length_prefixed {
+                return Err(syn::Error::new(
+                    Span::call_site(),
+                    "`length_prefixed` is only supported on enums",
+                ));
+            }
+        }
+        let input = Input { ast, attrs, body };
+
+        if let Some(by_key) = &input.attrs.by_key {
+            if let Body::Enum(_) = input.body {
+                return Err(syn::Error::new_spanned(
+                    &by_key.key,
+                    "`by_key` is only supported on structs",
+                ));
+            }
+            if input.key_field().is_none() {
+                return Err(syn::Error::new_spanned(
+                    &by_key.key,
+                    format!(
+                        "no field `{}` in `{}`",
+                        by_key.key.to_token_stream(),
+                        ast.ident
+                    ),
+                ));
+            }
+        }
+
+        // The projected context is passed to `Decoder::with_context`, which borrows the decoder
+        // mutably, so it can only borrow from a context that is itself a reference
+        let reference_context = !input.attrs.context_types.is_empty()
+            && input
+                .attrs
+                .context_types
+                .iter()
+                .all(|context_type| matches!(context_type.ty, Type::Reference(_)));
+        if !reference_context {
+            for field in input.fields() {
+                if let Some(ContextLens::Closure(closure)) = &field.attrs.context
+                    && let Some(borrow) = borrowed_projection(closure)
+                {
+                    return Err(syn::Error::new_spanned(
+                        borrow,
+                        "`context` cannot borrow from an owned context, as the decoder holds it \
+                         while the field is decoded; return an owned or cloned value, or decode \
+                         with a reference `context_type`",
+                    ));
+                }
+            }
+        }
+
+        Ok(input)
+    }
+
+    /// The field named by `by_key`, if any.
+    pub fn key_field(&self) -> Option<&Field<'a>> {
+        let key = &self.attrs.by_key.as_ref()?.key;
+        match &self.body {
+            Body::Struct(fields) => fields.fields.iter().find(|field| field.member == *key),
+            Body::Enum(_) => None,
+        }
+    }
+
+    /// All fields of the struct, or of every variant of the enum, in declaration order.
+    pub fn fields(&self) -> Vec<&Field<'a>> {
+        match &self.body {
+            Body::Struct(fields) => fields.fields.iter().collect(),
+            Body::Enum(variants) => variants
+                .iter()
+                .flat_map(|variant| variant.fields.fields.iter())
+                .collect(),
+        }
+    }
+
+    /// The types of the fields that are encoded through their own `Encode` impl, in declaration
+    /// order. `encode_with` and `lookup` fields are left out.
+    pub fn encoded_field_types(&self) -> Vec<&'a Type> {
+        self.wire_fields()
+            .into_iter()
+            .filter(|field| field.attrs.encode_with.is_none() && field.attrs.lookup.is_none())
+            .map(|field| field.ty)
+            .collect()
+    }
+
+    /// The types of the fields that are decoded through their own `Decode` impl with the impl's
+    /// context, in declaration order. `decode_with`, `lookup`, projected context and `context_from`
+    /// fields are left out.
+    pub fn decoded_field_types(&self) -> Vec<&'a Type> {
+        self.wire_fields()
+            .into_iter()
+            .filter(|field| {
+                field.attrs.decode_with.is_none()
+                    && field.attrs.lookup.is_none()
+                    && field.attrs.context.is_none()
+                    && field.sibling.is_none()
+            })
+            .map(|field| field.ty)
+            .collect()
+    }
+
+    /// The fields that are written to the stream, in declaration order: the `by_key` field alone,
+    /// or every field that is not skipped and not in a skipped variant.
+    pub fn wire_fields(&self) -> Vec<&Field<'a>> {
+        if self.attrs.by_key.is_some() {
+            return self.key_field().into_iter().collect();
+        }
+        let variants: Vec<&FieldList<'a>> = match &self.body {
+            Body::Struct(fields) => vec![fields],
+            Body::Enum(variants) => variants
+                .iter()
+                .filter(|variant| !variant.attrs.skip)
+                .map(|variant| &variant.fields)
+                .collect(),
+        };
+        variants
+            .into_iter()
+            .flat_map(|fields| fields.fields.iter())
+            .filter(|field| !field.attrs.is_skipped())
+            .collect()
+    }
+}
+
+/// Parses the variants of an enum and numbers the ones that are not skipped. A variant takes its
+/// `index`, its explicit discriminant or else the index after the previous variant's, like Rust
+/// discriminants do, so that variants can be added and reordered without changing the format.
+/// With `compat = "bincode_derive"`, variants are numbered by position instead, and with
+/// `tag = "name"` they are not numbered at all.
+fn variants<'a>(data_enum: &'a DataEnum, attrs: &ContainerAttrs) -> syn::Result<Vec<Variant<'a>>> {
+    let compat = attrs.compat == Some(Compat::BincodeDerive);
+    let tag = attrs.int_tag();
+    let mut variants = Vec::new();
+    let mut taken: Vec<(u32, &Ident)> = Vec::new();
+    let mut taken_names: Vec<(String, &Ident)> = Vec::new();
+    let mut next_index = Some(0u32);
+    let mut unknown: Option<&Ident> = None;
+    for variant in &data_enum.variants {
+        let variant_attrs = VariantAttrs::parse(&variant.attrs)?;
+        let ident = &variant.ident;
+        let name = match &variant_attrs.rename {
+            Some(rename) => rename.value(),
+            None => ident.to_string(),
+        };
+        if variant_attrs.unknown {
+            check_unknown(variant, &variant_attrs, attrs, unknown)?;
+            unknown = Some(ident);
+            variants.push(Variant {
+                ident,
+                attrs: variant_attrs,
+                fields: FieldList::parse(&variant.fields)?,
+                index: 0,
+                name,
+            });
+            continue;
+        }
+        if attrs.by_name() {
+            if variant_attrs.index.is_some() {
+                return Err(syn::Error::new_spanned(
+                    ident,
+                    "`index` has no effect with `tag = \"name\"`",
+                ));
+            }
+            if !variant_attrs.skip {
+                let aliases = variant_attrs.aliases.iter().map(LitStr::value);
+                for name in std::iter::once(name.clone()).chain(aliases) {
+                    if let Some((_, other)) = taken_names.iter().find(|(taken, _)| *taken == name) {
+                        return Err(syn::Error::new_spanned(
+                            ident,
+                            format!("variant `{ident}` has the same name \"{name}\" as `{other}`"),
+                        ));
+                    }
+                    taken_names.push((name, ident));
+                }
+            }
+            variants.push(Variant {
+                ident,
+                attrs: variant_attrs,
+                fields: FieldList::parse(&variant.fields)?,
+                index: 0,
+                name,
+            });
+            continue;
+        }
+        if variant_attrs.rename.is_some() || !variant_attrs.aliases.is_empty() {
+            return Err(syn::Error::new_spanned(
+                ident,
+                "`rename` and `alias` need `tag = \"name\"`",
+            ));
+        }
+        if compat && (variant_attrs.skip || variant_attrs.index.is_some()) {
+            return Err(syn::Error::new_spanned(
+                ident,
+                "`compat = \"bincode_derive\"` numbers variants by position and cannot be combined with `skip` or `index`",
+            ));
+        }
+        if variant_attrs.skip {
+            if variant_attrs.index.is_some() {
+                return Err(syn::Error::new_spanned(
+                    ident,
+                    format!("skipped variant `{ident}` cannot have an `index`"),
+                ));
+            }
+            variants.push(Variant {
+                ident,
+                attrs: variant_attrs,
+                fields: FieldList::parse(&variant.fields)?,
+                index: 0,
+                name,
+            });
+            continue;
+        }
+
+        let explicit = match (variant_attrs.index, &variant.discriminant) {
+            (Some(index), _) => Some(index),
+            (None, Some((_, discriminant))) if !compat => Some(literal_index(discriminant)?),
+            _ => None,
+        };
+        if explicit.is_none() && attrs.stable_indices {
+            return Err(syn::Error::new_spanned(
+                ident,
+                format!(
+                    "`stable_indices` requires an explicit index for `{ident}`, as `{ident} = N` or #[trait_decode(index = N)]"
+                ),
+            ));
+        }
+        let index = explicit.or(next_index).ok_or_else(|| {
+            syn::Error::new_spanned(ident, format!("the index of `{ident}` overflows `u32`"))
+        })?;
+        if u64::from(index) > tag.max {
+            return Err(syn::Error::new_spanned(
+                ident,
+                format!(
+                    "the index {index} of `{ident}` does not fit in the `{}` tag",
+                    tag.ty
+                ),
+            ));
+        }
+        if let Some((_, other)) = taken.iter().find(|(taken, _)| *taken == index) {
+            return Err(syn::Error::new_spanned(
+                ident,
+                format!("variant `{ident}` has the same index {index} as `{other}`"),
+            ));
+        }
+        taken.push((index, ident));
+        next_index = index.checked_add(1);
+
+        variants.push(Variant {
+            ident,
+            attrs: variant_attrs,
+            fields: FieldList::parse(&variant.fields)?,
+            index,
+            name,
+        });
+    }
+    Ok(variants)
+}
+
+/// Checks that an `unknown` variant is the only one, in a `length_prefixed` enum, and has a plain
+/// field for the tag and one for the payload.
+fn check_unknown(
+    variant: &syn::Variant,
+    variant_attrs: &VariantAttrs,
+    attrs: &ContainerAttrs,
+    previous: Option<&Ident>,
+) -> syn::Result<()> {
+    let ident = &variant.ident;
+    if let Some(previous) = previous {
+        return Err(syn::Error::new_spanned(
+            ident,
+            format!("`{previous}` is already the `unknown` variant"),
+        ));
+    }
+    if !attrs.length_prefixed {
+        return Err(syn::Error::new_spanned(
+            ident,
+            "an `unknown` variant needs `length_prefixed` on the enum, to know where the next value starts",
+        ));
+    }
+    if variant_attrs.skip
+        || variant_attrs.index.is_some()
+        || variant_attrs.rename.is_some()
+        || !variant_attrs.aliases.is_empty()
+    {
+        return Err(syn::Error::new_spanned(
+            ident,
+            "the `unknown` variant cannot have `skip`, `index`, `rename` or `alias`",
+        ));
+    }
+    let field_attrs = variant.fields.iter().flat_map(|field| &field.attrs);
+    if variant.fields.len() != 2
+        || field_attrs
+            .into_iter()
+            .any(|a| a.path().is_ident("trait_decode"))
+    {
+        return Err(syn::Error::new_spanned(
+            ident,
+            "the `unknown` variant needs a tag and a payload field without attributes, as in `Unknown { tag: u32, bytes: Vec<u8> }`",
+        ));
+    }
+    Ok(())
+}
+
+/// The index an explicit discriminant like `A = 5` or `A = b'a'` stands for.
+fn literal_index(discriminant: &Expr) -> syn::Result<u32> {
+    match discriminant {
+        Expr::Lit(ExprLit {
+            lit: Lit::Int(index),
+            ..
+        }) => index.base10_parse(),
+        Expr::Lit(ExprLit {
+            lit: Lit::Byte(byte),
+            ..
+        }) => Ok(byte.value().into()),
+        _ => Err(syn::Error::new_spanned(
+            discriminant,
+            "only non-negative integer and byte literal discriminants can be used as the variant index, use #[trait_decode(index = N)] instead",
+        )),
+    }
+}
+
+impl<'a> FieldList<'a> {
+    fn parse(style: &'a Fields) -> syn::Result<Self> {
+        let fields = style
+            .iter()
+            .enumerate()
+            .map(|(i, field)| {
+                let member = match &field.ident {
+                    Some(ident) => Member::Named(ident.clone()),
+                    None => Member::Unnamed(i.into()),
+                };
+                let attrs = FieldAttrs::parse(&field.attrs)?;
+                let collection = match &attrs.len {
+                    Some(_) => Some(Collection::of(&field.ty).ok_or_else(|| {
+                        syn::Error::new_spanned(
+                            &field.ty,
+                            "`len` is only supported on `Vec<T>`, `Box<[T]>` and `String` fields",
+                        )
+                    })?),
+                    None => None,
+                };
+                if attrs.condition.is_some() && !is_option(&field.ty) {
+                    return Err(syn::Error::new_spanned(
+                        &field.ty,
+                        "`if` is only supported on `Option<T>` fields",
+                    ));
+                }
+                Ok(Field {
+                    member,
+                    binding: binding(style, i),
+                    local: local(style, i),
+                    ty: &field.ty,
+                    attrs,
+                    sibling: None,
+                    collection,
+                })
+            })
+            .collect::<syn::Result<Vec<Field>>>()?;
+
+        // Resolve `context_from` siblings by the local they are decoded into
+        let siblings = fields
+            .iter()
+            .enumerate()
+            .map(|(i, field)| {
+                let Some(context_from) = &field.attrs.context_from else {
+                    return Ok(None);
+                };
+                let position = fields
+                    .iter()
+                    .position(|sibling| sibling.member == context_from.sibling)
+                    .ok_or_else(|| {
+                        syn::Error::new_spanned(
+                            &context_from.sibling,
+                            format!(
+                                "no field `{}` to decode with",
+                                context_from.sibling.to_token_stream()
+                            ),
+                        )
+                    })?;
+                // Fields are decoded in declaration order, which is also the order they are
+                // written in, so the sibling has to come first
+                if position >= i {
+                    return Err(syn::Error::new_spanned(
+                        &context_from.sibling,
+                        format!(
+                            "`{}` must be declared before `{}` to decode it with",
+                            context_from.sibling.to_token_stream(),
+                            field.member.to_token_stream()
+                        ),
+                    ));
+                }
+                Ok(Some((position, context_from.by_ref)))
+            })
+            .collect::<syn::Result<Vec<_>>>()?;
+        let fields = fields
+            .into_iter()
+            .zip(&siblings)
+            .map(|(field, sibling)| Field {
+                sibling: sibling.map(|(position, by_ref)| Sibling {
+                    local: local(style, position),
+                    ty: &style.iter().nth(position).expect("sibling was found").ty,
+                    by_ref,
+                }),
+                ..field
+            })
+            .collect();
+
+        Ok(FieldList { style, fields })
+    }
+}
+
+/// The `&...` a `context = |ctx| &...` closure returns, if it returns a borrow.
+fn borrowed_projection(closure: &Expr) -> Option<&Expr> {
+    let Expr::Closure(closure) = closure else {
+        return None;
+    };
+    let mut body = &*closure.body;
+    loop {
+        body = match body {
+            Expr::Paren(paren) => &paren.expr,
+            Expr::Block(block) => match block.block.stmts.last() {
+                Some(Stmt::Expr(expr, None)) => expr,
+                _ => return None,
+            },
+            Expr::Reference(_) => return Some(body),
+            _ => return None,
+        };
+    }
+}
+
+/// The name expressions refer to a field by.
+fn binding(style: &Fields, position: usize) -> Ident {
+    let field = style.iter().nth(position).expect("field exists");
+    match &field.ident {
+        Some(ident) => ident.clone(),
+        None => Ident::new(&format!("field{}", position), field.span()),
+    }
+}
+
+/// The local a field is decoded into.
+fn local(style: &Fields, position: usize) -> Ident {
+    let field = style.iter().nth(position).expect("field exists");
+    match &field.ident {
+        Some(ident) => Ident::new(&format!("__field_{}", ident.unraw()), field.span()),
+        None => Ident::new(&format!("__field_{}", position), field.span()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn indices(ast: &DeriveInput) -> Vec<u32> {
+        let Ok(input) = Input::parse(ast) else {
+            panic!("`{}` did not parse", ast.ident);
+        };
+        let Body::Enum(variants) = input.body else {
+            panic!("`{}` is not an enum", ast.ident);
+        };
+        variants.iter().map(|variant| variant.index).collect()
+    }
+
+    fn error(ast: &DeriveInput) -> String {
+        match Input::parse(ast) {
+            Ok(_) => panic!("`{}` parsed", ast.ident),
+            Err(err) => err.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_byte_literal_discriminants() {
+        let ast = syn::parse_quote! {
+            #[repr(u8)]
+            enum Letter {
+                A = b'a',
+                B,
+                Z = b'z',
+            }
+        };
+        assert_eq!(indices(&ast), [97, 98, 122]);
+    }
+
+    #[test]
+    fn test_unreadable_discriminants() {
+        let message = "only non-negative integer and byte literal discriminants can be used as the \
+                       variant index, use #[trait_decode(index = N)] instead";
+        let constant = syn::parse_quote! {
+            enum Constant {
+                A = K,
+            }
+        };
+        assert_eq!(error(&constant), message);
+        let negative = syn::parse_quote! {
+            #[repr(i8)]
+            enum Negative {
+                A = -1,
+            }
+        };
+        assert_eq!(error(&negative), message);
+
+        // The index overrides the discriminant
+        let indexed = syn::parse_quote! {
+            #[repr(i8)]
+            enum Indexed {
+                #[trait_decode(index = 0)]
+                A = -1,
+                B,
+            }
+        };
+        assert_eq!(indices(&indexed), [0, 1]);
+    }
+}
@@ -1,5 +1,5 @@
 use std::marker::PhantomData;
-use bincode_trait_derive::Encode;
+use bincode_trait_derive::{BorrowDecodeFromDecode, Decode, Encode};
 
 // Create a Ring trait and implementation for testing
 pub trait Ring {
@@ -13,14 +13,6 @@ pub trait MonomialOrder {}
 pub struct LexOrder {}
 impl MonomialOrder for LexOrder {}
 
-// Implement Encode for LexOrder
-impl bincode::Encode for LexOrder {
-    fn encode<E: bincode::enc::Encoder>(&self, _encoder: &mut E) -> Result<(), bincode::error::EncodeError> {
-        // LexOrder doesn't have any data that needs encoding
-        Ok(())
-    }
-}
-
 // Test Ring implementation 
 pub struct TestRing {}
 impl Ring for TestRing {
@@ -35,8 +27,14 @@ impl bincode::Encode for TestRing {
     }
 }
 
-// The MultivariatePolynomial struct with derive(Encode)
-#[derive(Encode)]
+impl<C> bincode::Decode<C> for TestRing {
+    fn decode<D: bincode::de::Decoder<Context = C>>(_decoder: &mut D) -> Result<Self, bincode::error::DecodeError> {
+        Ok(TestRing {})
+    }
+}
+
+// The MultivariatePolynomial struct with derive(Encode, Decode)
+#[derive(Encode, Decode, BorrowDecodeFromDecode)]
 pub struct MultivariatePolynomial<F: Ring, E: Exponent = u16, O: MonomialOrder = LexOrder> {
     pub coefficients: Vec<F::Element>,
     pub exponents: Vec<E>,
@@ -56,4 +54,24 @@ fn test_multivariate_polynomial_encode() {
     // Try to encode the polynomial
     let encoded: Vec<u8> = bincode::encode_to_vec(poly, bincode::config::standard()).unwrap();
     assert!(!encoded.is_empty());
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_multivariate_polynomial_round_trip() {
+    let poly = MultivariatePolynomial {
+        coefficients: vec![1, 2, 3],
+        exponents: vec![1u16, 2u16, 3u16],
+        ring: TestRing {},
+        _phantom: PhantomData::<LexOrder>,
+    };
+
+    let encoded: Vec<u8> = bincode::encode_to_vec(&poly, bincode::config::standard()).unwrap();
+    let (decoded, _): (MultivariatePolynomial<TestRing>, usize) =
+        bincode::decode_from_slice(&encoded, bincode::config::standard()).unwrap();
+    assert_eq!(decoded.coefficients, poly.coefficients);
+    assert_eq!(decoded.exponents, poly.exponents);
+
+    let (borrowed, _): (MultivariatePolynomial<TestRing>, usize) =
+        bincode::borrow_decode_from_slice(&encoded, bincode::config::standard()).unwrap();
+    assert_eq!(borrowed.coefficients, poly.coefficients);
+}
@@ -0,0 +1,90 @@
+use std::marker::PhantomData;
+
+use bincode_trait_derive::{BorrowDecodeFromDecode, Decode, Encode};
+
+pub trait Ring {
+    type Element;
+}
+
+// Neither Encode nor Decode: only its element type is ever written
+pub struct IntegerRing;
+
+impl Ring for IntegerRing {
+    type Element = i64;
+}
+
+// Neither Encode nor Decode, only used through `PhantomData`
+pub struct LexOrder;
+
+#[derive(Debug, PartialEq, Encode, Decode, BorrowDecodeFromDecode)]
+pub struct Monomials<E, O> {
+    pub exponents: Vec<E>,
+    pub _phantom: PhantomData<O>,
+}
+
+#[derive(Encode, Decode, BorrowDecodeFromDecode)]
+pub struct Coefficients<F: Ring> {
+    pub coefficients: Vec<F::Element>,
+}
+
+#[derive(Debug, Clone)]
+pub struct TestContext {
+    pub version: u32,
+}
+
+// Bounds on generics refer to the concrete context rather than `__Context`
+#[derive(Encode, Decode, BorrowDecodeFromDecode)]
+#[trait_decode(context_type = TestContext)]
+pub struct WithContext<F: Ring, T> {
+    pub element: F::Element,
+    pub value: T,
+}
+
+#[test]
+fn test_phantom_parameter_is_not_bounded() {
+    let monomials = Monomials::<u16, LexOrder> {
+        exponents: vec![1, 2, 3],
+        _phantom: PhantomData,
+    };
+
+    let encoded: Vec<u8> = bincode::encode_to_vec(&monomials, bincode::config::standard()).unwrap();
+    let (decoded, _): (Monomials<u16, LexOrder>, usize) =
+        bincode::decode_from_slice(&encoded, bincode::config::standard()).unwrap();
+    assert_eq!(decoded.exponents, monomials.exponents);
+
+    let (borrowed, _): (Monomials<u16, LexOrder>, usize) =
+        bincode::borrow_decode_from_slice(&encoded, bincode::config::standard()).unwrap();
+    assert_eq!(borrowed.exponents, monomials.exponents);
+}
+
+#[test]
+fn test_projection_root_is_not_bounded() {
+    let coefficients = Coefficients::<IntegerRing> {
+        coefficients: vec![-1, 0, 1],
+    };
+
+    let encoded: Vec<u8> =
+        bincode::encode_to_vec(&coefficients, bincode::config::standard()).unwrap();
+    let (decoded, _): (Coefficients<IntegerRing>, usize) =
+        bincode::decode_from_slice(&encoded, bincode::config::standard()).unwrap();
+    assert_eq!(decoded.coefficients, coefficients.coefficients);
+}
+
+#[test]
+fn test_concrete_context_with_generics() {
+    let value = WithContext::<IntegerRing, String> {
+        element: 5,
+        value: "five".to_string(),
+    };
+
+    let encoded: Vec<u8> = bincode::encode_to_vec(&value, bincode::config::standard()).unwrap();
+    let (decoded, _): (WithContext<IntegerRing, String>, usize) =
+        bincode::decode_from_slice_with_context(
+            &encoded,
+            bincode::config::standard(),
+            TestContext { version: 1 },
+        )
+        .unwrap();
+    assert_eq!(decoded.element, 5);
+    assert_eq!(decoded.value, "five");
+}
@@ -0,0 +1,92 @@
+use std::marker::PhantomData;
+
+use bincode_trait_derive::{BorrowDecodeFromDecode, Decode, Encode};
+
+#[derive(Debug, Clone)]
+pub struct TestContext {
+    pub version: u32,
+}
+
+pub trait Versioned {
+    fn version(&self) -> u32;
+}
+
+impl Versioned for TestContext {
+    fn version(&self) -> u32 {
+        self.version
+    }
+}
+
+// Neither Encode nor Decode, only ever used as a marker
+pub struct Marker;
+
+// The marker parameter would otherwise get an `M: Encode` / `M: Decode<__Context>` bound
+#[derive(Debug, PartialEq, Encode, Decode, BorrowDecodeFromDecode)]
+#[trait_decode(bound(
+    encode = "T: ::bincode::Encode",
+    decode = "T: ::bincode::Decode<__Context> + Clone"
+))]
+pub struct Tagged<T, M> {
+    pub value: T,
+    pub _marker: PhantomData<M>,
+}
+
+// An empty bound removes all inferred predicates
+#[derive(Debug, PartialEq, Encode, Decode)]
+#[trait_decode(context_type = TestContext, bound(encode = "", decode = ""))]
+pub struct Unbounded<M> {
+    pub id: u32,
+    pub _marker: PhantomData<M>,
+}
+
+// `add_bound` keeps the inferred predicates and adds ones mentioning the context
+#[derive(Debug, PartialEq, Encode, Decode, BorrowDecodeFromDecode)]
+#[trait_decode(add_bound(decode = "__Context: Versioned"))]
+pub struct Extended<T> {
+    pub value: T,
+}
+
+#[test]
+fn test_replaced_bounds() {
+    let test = Tagged::<u32, Marker> {
+        value: 7,
+        _marker: PhantomData,
+    };
+    let encoded: Vec<u8> = bincode::encode_to_vec(&test, bincode::config::standard()).unwrap();
+    let (decoded, _): (Tagged<u32, Marker>, usize) =
+        bincode::decode_from_slice(&encoded, bincode::config::standard()).unwrap();
+    assert_eq!(decoded.value, 7);
+
+    let (borrowed, _): (Tagged<u32, Marker>, usize) =
+        bincode::borrow_decode_from_slice(&encoded, bincode::config::standard()).unwrap();
+    assert_eq!(borrowed.value, 7);
+}
+
+#[test]
+fn test_empty_bounds() {
+    let test = Unbounded::<Marker> {
+        id: 3,
+        _marker: PhantomData,
+    };
+    let encoded: Vec<u8> = bincode::encode_to_vec(&test, bincode::config::standard()).unwrap();
+    let (decoded, _): (Unbounded<Marker>, usize) = bincode::decode_from_slice_with_context(
+        &encoded,
+        bincode::config::standard(),
+        TestContext { version: 1 },
+    )
+    .unwrap();
+    assert_eq!(decoded.id, 3);
+}
+
+#[test]
+fn test_additional_bounds() {
+    let test = Extended { value: 11u64 };
+    let encoded: Vec<u8> = bincode::encode_to_vec(&test, bincode::config::standard()).unwrap();
+    let (decoded, _): (Extended<u64>, usize) = bincode::decode_from_slice_with_context(
+        &encoded,
+        bincode::config::standard(),
+        TestContext { version: 2 },
+    )
+    .unwrap();
+    assert_eq!(decoded, test);
+}
@@ -0,0 +1,133 @@
+use std::collections::HashMap;
+
+use bincode::error::DecodeError;
+use bincode_trait_derive::{BorrowDecodeFromDecode, Decode, Encode};
+
+pub struct Vertices {
+    pub vertices: Vec<Vertex>,
+}
+
+impl Vertices {
+    fn vertex(&self, name: String) -> Option<Vertex> {
+        self.vertices.iter().find(|v| v.name == name).cloned()
+    }
+}
+
+pub trait Model {
+    fn vertices(&self) -> &Vertices;
+}
+
+impl Model for Vertices {
+    fn vertices(&self) -> &Vertices {
+        self
+    }
+}
+
+// Without `trait`, the context is bounded by the trait `registry` is a method of
+#[derive(Debug, Clone, PartialEq, Encode, Decode, BorrowDecodeFromDecode)]
+#[trait_decode(by_key = name, registry = Model::vertices, find = vertex)]
+pub struct Vertex {
+    pub name: String,
+    pub couplings: Vec<u32>,
+}
+
+pub struct Colours {
+    pub colours: HashMap<u8, Colour>,
+}
+
+impl Colours {
+    fn colour(&self, index: u8) -> Option<Colour> {
+        self.colours.get(&index).copied()
+    }
+}
+
+// Without `registry`, the key is looked up on the context itself
+#[derive(Debug, Clone, Copy, PartialEq, Encode, Decode, BorrowDecodeFromDecode)]
+#[trait_decode(context_type = Colours, by_key = 0, find = colour)]
+pub struct Colour(pub u8, pub u32);
+
+#[derive(Debug, PartialEq, Encode, Decode, BorrowDecodeFromDecode)]
+#[trait_decode(context_type = Vertices, infer)]
+pub struct Diagram {
+    pub vertices: Vec<Vertex>,
+    pub loops: u32,
+}
+
+fn vertices() -> Vertices {
+    Vertices {
+        vertices: vec![
+            Vertex {
+                name: "qqg".to_string(),
+                couplings: vec![1, 2],
+            },
+            Vertex {
+                name: "ggg".to_string(),
+                couplings: vec![3],
+            },
+        ],
+    }
+}
+
+#[test]
+fn test_by_key_round_trip() {
+    let vertex = vertices().vertices[1].clone();
+    let encoded = bincode::encode_to_vec(&vertex, bincode::config::standard()).unwrap();
+    assert_eq!(
+        encoded,
+        bincode::encode_to_vec("ggg", bincode::config::standard()).unwrap()
+    );
+
+    let (decoded, _): (Vertex, usize) = bincode::borrow_decode_from_slice_with_context(
+        &encoded,
+        bincode::config::standard(),
+        vertices(),
+    )
+    .unwrap();
+    assert_eq!(decoded, vertex);
+}
+
+#[test]
+fn test_by_key_tuple_struct() {
+    let red = Colour(1, 0xff0000);
+    let colours = Colours {
+        colours: HashMap::from([(red.0, red)]),
+    };
+
+    let encoded = bincode::encode_to_vec(red, bincode::config::standard()).unwrap();
+    assert_eq!(encoded, [1]);
+    let (decoded, _): (Colour, usize) =
+        bincode::decode_from_slice_with_context(&encoded, bincode::config::standard(), colours)
+            .unwrap();
+    assert_eq!(decoded, red);
+}
+
+#[test]
+fn test_by_key_fields_in_context_type() {
+    let diagram = Diagram {
+        vertices: vertices().vertices,
+        loops: 2,
+    };
+
+    let encoded = bincode::encode_to_vec(&diagram, bincode::config::standard()).unwrap();
+    let (decoded, _): (Diagram, usize) =
+        bincode::decode_from_slice_with_context(&encoded, bincode::config::standard(), vertices())
+            .unwrap();
+    assert_eq!(decoded, diagram);
+}
+
+#[test]
+fn test_missing_key_is_an_error() {
+    let encoded = bincode::encode_to_vec("uuz", bincode::config::standard()).unwrap();
+    let err = bincode::decode_from_slice_with_context::<_, Vertex, _>(
+        &encoded,
+        bincode::config::standard(),
+        vertices(),
+    )
+    .unwrap_err();
+    match err {
+        DecodeError::OtherString(message) => {
+            assert_eq!(message, "cannot resolve `Vertex`: no entry for key \"uuz\"")
+        }
+        err => panic!("unexpected error {err:?}"),
+    }
+}
@@ -0,0 +1,134 @@
+use bincode::error::EncodeError;
+use bincode_trait_derive::{BorrowDecodeFromDecode, Decode, Encode};
+
+pub const HAS_CHECKSUM: u8 = 0b01;
+pub const HAS_NAME: u8 = 0b10;
+
+#[derive(Debug, PartialEq, Encode, Decode, BorrowDecodeFromDecode)]
+pub struct Header {
+    pub version: u16,
+    pub flags: u8,
+    #[trait_decode(if = flags & HAS_CHECKSUM != 0)]
+    pub checksum: Option<u32>,
+    #[trait_decode(if = flags & HAS_NAME != 0)]
+    pub name: Option<String>,
+    #[trait_decode(if = *version >= 2)]
+    pub extensions: Option<Vec<u8>>,
+}
+
+pub struct Reader {
+    pub version: u16,
+}
+
+fn supports_comments(reader: &Reader) -> bool {
+    reader.version >= 3
+}
+
+#[derive(Debug, PartialEq, Encode, Decode, BorrowDecodeFromDecode)]
+#[trait_decode(context_type = Reader)]
+pub enum Record {
+    Entry(
+        u32,
+        #[trait_decode(if_with = supports_comments)] Option<String>,
+    ),
+}
+
+fn encode<T: bincode::Encode>(value: T) -> Vec<u8> {
+    bincode::encode_to_vec(value, bincode::config::standard()).unwrap()
+}
+
+#[test]
+fn test_conditional_fields() {
+    let header = Header {
+        version: 1,
+        flags: HAS_NAME,
+        checksum: None,
+        name: Some("a".to_string()),
+        extensions: None,
+    };
+    let encoded = encode(&header);
+    assert_eq!(encoded, encode((1u16, HAS_NAME, "a")));
+
+    let (decoded, _): (Header, usize) =
+        bincode::borrow_decode_from_slice(&encoded, bincode::config::standard()).unwrap();
+    assert_eq!(decoded, header);
+
+    let header = Header {
+        version: 2,
+        flags: HAS_CHECKSUM,
+        checksum: Some(0xdead),
+        name: None,
+        extensions: Some(vec![1, 2]),
+    };
+    let (decoded, _): (Header, usize) =
+        bincode::decode_from_slice(&encode(&header), bincode::config::standard()).unwrap();
+    assert_eq!(decoded, header);
+}
+
+#[test]
+fn test_value_must_agree_with_condition() {
+    let header = Header {
+        version: 1,
+        flags: 0,
+        checksum: Some(1),
+        name: None,
+        extensions: None,
+    };
+    let err = bincode::encode_to_vec(&header, bincode::config::standard()).unwrap_err();
+    assert!(matches!(
+        err,
+        EncodeError::Other("`Header.checksum` is `Some` but its `if` condition does not hold")
+    ));
+
+    let header = Header {
+        version: 2,
+        flags: 0,
+        checksum: None,
+        name: None,
+        extensions: None,
+    };
+    let err = bincode::encode_to_vec(&header, bincode::config::standard()).unwrap_err();
+    assert!(matches!(
+        err,
+        EncodeError::Other("`Header.extensions` is `None` but its `if` condition holds")
+    ));
+}
+
+#[test]
+fn test_condition_from_context() {
+    let record = Record::Entry(7, Some("note".to_string()));
+    let encoded = encode(&record);
+    let (decoded, _): (Record, usize) = bincode::decode_from_slice_with_context(
+        &encoded,
+        bincode::config::standard(),
+        Reader { version: 3 },
+    )
+    .unwrap();
+    assert_eq!(decoded, record);
+
+    let record = Record::Entry(7, None);
+    let encoded = encode(&record);
+    let (decoded, _): (Record, usize) = bincode::decode_from_slice_with_context(
+        &encoded,
+        bincode::config::standard(),
+        Reader { version: 2 },
+    )
+    .unwrap();
+    assert_eq!(decoded, record);
+}
+
+#[test]
+fn test_condition_from_context_is_not_checked_when_encoding() {
+    // Written as if comments are supported, and read by a reader that does not support them
+    let record = Record::Entry(7, Some("note".to_string()));
+    let encoded = encode(&record);
+    let (decoded, read): (Record, usize) = bincode::decode_from_slice_with_context(
+        &encoded,
+        bincode::config::standard(),
+        Reader { version: 2 },
+    )
+    .unwrap();
+    // The comment is left in the stream, for whatever is decoded next
+    assert_eq!(decoded, Record::Entry(7, None));
+    assert!(read < encoded.len());
+}
@@ -0,0 +1,118 @@
+use bincode::de::Decoder;
+use bincode::error::DecodeError;
+use bincode_trait_derive::{BorrowDecodeFromDecode, Decode, Encode};
+
+pub trait Ring {
+    type Element;
+}
+
+#[derive(Debug, Clone, PartialEq, Encode, Decode, BorrowDecodeFromDecode)]
+pub struct Zp {
+    pub modulus: u64,
+}
+
+impl Ring for Zp {
+    type Element = ZpElement;
+}
+
+/// An element of `Zp`, which can only be decoded once the modulus is known.
+#[derive(Debug, PartialEq, Encode)]
+pub struct ZpElement(pub u64);
+
+impl<'r> bincode::Decode<&'r Zp> for ZpElement {
+    fn decode<D: Decoder<Context = &'r Zp>>(decoder: &mut D) -> Result<Self, DecodeError> {
+        let value: u64 = bincode::Decode::decode(decoder)?;
+        let modulus = decoder.context().modulus;
+        if value >= modulus {
+            return Err(DecodeError::Other("element out of range"));
+        }
+        Ok(ZpElement(value))
+    }
+}
+
+#[derive(Debug, PartialEq, Encode, Decode, BorrowDecodeFromDecode)]
+pub struct Polynomial<F: Ring> {
+    pub ring: F,
+    #[trait_decode(context_from = &ring)]
+    pub coefficients: Vec<F::Element>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Encode, Decode, BorrowDecodeFromDecode)]
+pub struct Modulus(pub u32);
+
+#[derive(Debug, PartialEq, Encode)]
+pub struct Residue(pub u32);
+
+impl bincode::Decode<Modulus> for Residue {
+    fn decode<D: Decoder<Context = Modulus>>(decoder: &mut D) -> Result<Self, DecodeError> {
+        let value: u32 = bincode::Decode::decode(decoder)?;
+        Ok(Residue(value % decoder.context().0))
+    }
+}
+
+// The sibling does not have to come right before, and is cloned without `&`
+#[derive(Debug, PartialEq, Encode, Decode, BorrowDecodeFromDecode)]
+pub struct Residues {
+    pub modulus: Modulus,
+    pub label: String,
+    #[trait_decode(context_from = modulus)]
+    pub values: Vec<Residue>,
+}
+
+#[derive(Debug, PartialEq, Encode, Decode, BorrowDecodeFromDecode)]
+pub enum Value {
+    Integer(i64),
+    Element(Zp, #[trait_decode(context_from = &0)] ZpElement),
+}
+
+fn encode<T: bincode::Encode>(value: T) -> Vec<u8> {
+    bincode::encode_to_vec(value, bincode::config::standard()).unwrap()
+}
+
+#[test]
+fn test_decode_with_sibling_reference() {
+    let polynomial = Polynomial {
+        ring: Zp { modulus: 7 },
+        coefficients: vec![ZpElement(3), ZpElement(6)],
+    };
+    let encoded = encode(&polynomial);
+    assert_eq!(encoded, encode((7u64, vec![3u64, 6])));
+
+    let (decoded, _): (Polynomial<Zp>, usize) =
+        bincode::borrow_decode_from_slice(&encoded, bincode::config::standard()).unwrap();
+    assert_eq!(decoded, polynomial);
+
+    let err = bincode::decode_from_slice::<Polynomial<Zp>, _>(
+        &encode((5u64, vec![3u64, 6])),
+        bincode::config::standard(),
+    )
+    .unwrap_err();
+    assert!(matches!(err, DecodeError::Other("element out of range")));
+}
+
+#[test]
+fn test_sibling_by_value() {
+    let residues = Residues {
+        modulus: Modulus(5),
+        label: "mod 5".to_string(),
+        values: vec![Residue(1), Residue(4)],
+    };
+    let encoded = encode(&residues);
+    assert_eq!(encoded, encode((5u32, "mod 5", vec![1u32, 4])));
+
+    let (decoded, _): (Residues, usize) = bincode::decode_from_slice(
+        &encode((5u32, "mod 5", vec![6u32, 4])),
+        bincode::config::standard(),
+    )
+    .unwrap();
+    assert_eq!(decoded, residues);
+}
+
+#[test]
+fn test_sibling_in_tuple_variant() {
+    let value = Value::Element(Zp { modulus: 11 }, ZpElement(10));
+    let encoded = encode(&value);
+    let (decoded, _): (Value, usize) =
+        bincode::decode_from_slice(&encoded, bincode::config::standard()).unwrap();
+    assert_eq!(decoded, value);
+}
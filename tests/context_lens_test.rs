@@ -0,0 +1,192 @@
+use std::sync::Arc;
+
+use bincode_trait_derive::{BorrowDecodeFromDecode, Decode, Encode};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Names {
+    pub names: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Units {
+    pub unit: String,
+}
+
+pub struct Session {
+    pub names: Names,
+    pub units: Arc<Units>,
+}
+
+/// Resolves a name from its index in `&Names`.
+#[derive(Debug, PartialEq, Encode, Decode, BorrowDecodeFromDecode)]
+#[trait_decode(context_type = &'a Names)]
+pub struct Label {
+    pub index: u32,
+    #[trait_decode(default_with = label_name)]
+    pub name: String,
+}
+
+fn label_name(names: &&Names) -> String {
+    names.names.first().cloned().unwrap_or_default()
+}
+
+/// Needs an owned `Arc<Units>` context.
+#[derive(Debug, PartialEq, Encode, Decode, BorrowDecodeFromDecode)]
+#[trait_decode(context_type = Arc<Units>)]
+pub struct Quantity {
+    pub value: f64,
+    #[trait_decode(from_context = unit_of)]
+    pub unit: String,
+}
+
+fn unit_of(units: &Arc<Units>) -> String {
+    units.unit.clone()
+}
+
+// An owned projection works with any parent context, here a clone of an `Arc`
+#[derive(Debug, PartialEq, Encode, Decode, BorrowDecodeFromDecode)]
+#[trait_decode(context_type = Session)]
+pub struct Measurement {
+    #[trait_decode(context = |session| session.units.clone())]
+    pub quantity: Quantity,
+    pub count: u32,
+}
+
+/// Like `Label`, but with an owned `Names` context.
+#[derive(Debug, PartialEq, Encode, Decode, BorrowDecodeFromDecode)]
+#[trait_decode(context_type = Names)]
+pub struct Entry {
+    pub index: u32,
+    #[trait_decode(default_with = entry_name)]
+    pub name: String,
+}
+
+fn entry_name(names: &Names) -> String {
+    names.names.first().cloned().unwrap_or_default()
+}
+
+// A projection cannot borrow from an owned parent context, `|session| &session.names` is rejected,
+// so the field takes a clone instead
+#[derive(Debug, PartialEq, Encode, Decode, BorrowDecodeFromDecode)]
+#[trait_decode(context_type = Session)]
+pub struct Caption {
+    #[trait_decode(context = |session| session.names.clone())]
+    pub entry: Entry,
+}
+
+// When the parent context is a reference, the projection can borrow through it
+#[derive(Debug, PartialEq, Encode, Decode, BorrowDecodeFromDecode)]
+#[trait_decode(context_type = &'a Session)]
+pub enum Annotation {
+    Label(#[trait_decode(context = |session| &session.names)] Label),
+    Measurement {
+        #[trait_decode(context_via = session_units)]
+        quantity: Quantity,
+    },
+}
+
+fn session_units(session: &&Session) -> Arc<Units> {
+    session.units.clone()
+}
+
+pub trait HasUnits {
+    fn units(&self) -> Arc<Units>;
+}
+
+impl HasUnits for Session {
+    fn units(&self) -> Arc<Units> {
+        self.units.clone()
+    }
+}
+
+// `context_via = Trait::method` bounds a generic context by `Trait`
+#[derive(Debug, PartialEq, Encode, Decode, BorrowDecodeFromDecode)]
+pub struct Reading {
+    #[trait_decode(context_via = HasUnits::units)]
+    pub quantity: Quantity,
+}
+
+fn session() -> Session {
+    Session {
+        names: Names {
+            names: vec!["mass".to_string()],
+        },
+        units: Arc::new(Units {
+            unit: "GeV".to_string(),
+        }),
+    }
+}
+
+fn quantity() -> Quantity {
+    Quantity {
+        value: 125.1,
+        unit: "GeV".to_string(),
+    }
+}
+
+#[test]
+fn test_owned_projection() {
+    let measurement = Measurement {
+        quantity: quantity(),
+        count: 2,
+    };
+    let encoded = bincode::encode_to_vec(&measurement, bincode::config::standard()).unwrap();
+    let (decoded, _): (Measurement, usize) =
+        bincode::decode_from_slice_with_context(&encoded, bincode::config::standard(), session())
+            .unwrap();
+    assert_eq!(decoded, measurement);
+}
+
+#[test]
+fn test_cloned_projection_of_owned_context() {
+    let caption = Caption {
+        entry: Entry {
+            index: 0,
+            name: "mass".to_string(),
+        },
+    };
+    let encoded = bincode::encode_to_vec(&caption, bincode::config::standard()).unwrap();
+    assert_eq!(
+        encoded,
+        bincode::encode_to_vec(0u32, bincode::config::standard()).unwrap()
+    );
+    let (decoded, _): (Caption, usize) =
+        bincode::decode_from_slice_with_context(&encoded, bincode::config::standard(), session())
+            .unwrap();
+    assert_eq!(decoded, caption);
+}
+
+#[test]
+fn test_projection_of_reference_context() {
+    let session = session();
+    for annotation in [
+        Annotation::Label(Label {
+            index: 0,
+            name: "mass".to_string(),
+        }),
+        Annotation::Measurement {
+            quantity: quantity(),
+        },
+    ] {
+        let encoded = bincode::encode_to_vec(&annotation, bincode::config::standard()).unwrap();
+        let (decoded, _): (Annotation, usize) = bincode::borrow_decode_from_slice_with_context(
+            &encoded,
+            bincode::config::standard(),
+            &session,
+        )
+        .unwrap();
+        assert_eq!(decoded, annotation);
+    }
+}
+
+#[test]
+fn test_projection_through_trait() {
+    let reading = Reading {
+        quantity: quantity(),
+    };
+    let encoded = bincode::encode_to_vec(&reading, bincode::config::standard()).unwrap();
+    let (decoded, _): (Reading, usize) =
+        bincode::decode_from_slice_with_context(&encoded, bincode::config::standard(), session())
+            .unwrap();
+    assert_eq!(decoded, reading);
+}
@@ -0,0 +1,231 @@
+use std::marker::PhantomData;
+
+use bincode_trait_derive::{BorrowDecodeFromDecode, Decode, Encode};
+
+pub struct Names {
+    pub names: Vec<String>,
+}
+
+pub struct Units {
+    pub units: Vec<String>,
+}
+
+pub trait NameLookup {
+    fn name(&self, id: u32) -> Option<&str>;
+}
+
+impl NameLookup for Names {
+    fn name(&self, id: u32) -> Option<&str> {
+        self.names.get(id as usize).map(String::as_str)
+    }
+}
+
+impl<T: NameLookup + ?Sized> NameLookup for &T {
+    fn name(&self, id: u32) -> Option<&str> {
+        (**self).name(id)
+    }
+}
+
+impl<A: NameLookup, B> NameLookup for (A, B) {
+    fn name(&self, id: u32) -> Option<&str> {
+        self.0.name(id)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Name {
+    pub id: u32,
+    pub name: String,
+}
+
+impl bincode::Encode for Name {
+    fn encode<E: bincode::enc::Encoder>(
+        &self,
+        encoder: &mut E,
+    ) -> Result<(), bincode::error::EncodeError> {
+        self.id.encode(encoder)
+    }
+}
+
+impl<C: NameLookup> bincode::Decode<C> for Name {
+    fn decode<D: bincode::de::Decoder<Context = C>>(
+        decoder: &mut D,
+    ) -> Result<Self, bincode::error::DecodeError> {
+        let id = u32::decode(decoder)?;
+        let name = decoder
+            .context()
+            .name(id)
+            .ok_or(bincode::error::DecodeError::Other("unknown name"))?;
+        Ok(Name {
+            id,
+            name: name.to_string(),
+        })
+    }
+}
+
+#[derive(Debug, PartialEq, Encode, Decode, BorrowDecodeFromDecode)]
+#[trait_decode(context_type = &'a Names)]
+pub struct Borrowed {
+    pub name: Name,
+}
+
+#[derive(Debug, PartialEq, Encode, Decode, BorrowDecodeFromDecode)]
+#[trait_decode(context_type = &Names)]
+pub enum Elided {
+    Named(Name),
+    Anonymous,
+}
+
+#[derive(Debug, PartialEq, Encode, Decode, BorrowDecodeFromDecode)]
+#[trait_decode(context_type = (&'a Names, &'b Units))]
+pub struct Tupled {
+    pub name: Name,
+    pub count: u32,
+}
+
+#[derive(Debug, PartialEq, Encode, Decode, BorrowDecodeFromDecode)]
+#[trait_decode(context_type = <C: NameLookup> &'a C)]
+pub struct Introduced {
+    pub name: Name,
+}
+
+pub struct Registry<T> {
+    pub default: T,
+}
+
+#[derive(Debug, PartialEq, Encode, Decode, BorrowDecodeFromDecode)]
+#[trait_decode(context_type = Registry<T>)]
+pub struct Registered<T> {
+    pub value: T,
+}
+
+fn names() -> Names {
+    Names {
+        names: vec!["squark".to_string(), "gluino".to_string()],
+    }
+}
+
+fn round_trip<T, C>(value: &T, context: C) -> T
+where
+    T: bincode::Encode + bincode::Decode<C>,
+{
+    let encoded: Vec<u8> = bincode::encode_to_vec(value, bincode::config::standard()).unwrap();
+    bincode::decode_from_slice_with_context(&encoded, bincode::config::standard(), context)
+        .unwrap()
+        .0
+}
+
+fn borrow_round_trip<T, C>(value: &T, context: C) -> T
+where
+    T: bincode::Encode + for<'de> bincode::BorrowDecode<'de, C>,
+{
+    let encoded: Vec<u8> = bincode::encode_to_vec(value, bincode::config::standard()).unwrap();
+    bincode::borrow_decode_from_slice_with_context(&encoded, bincode::config::standard(), context)
+        .unwrap()
+        .0
+}
+
+fn gluino() -> Name {
+    Name {
+        id: 1,
+        name: "gluino".to_string(),
+    }
+}
+
+#[test]
+fn test_reference_context() {
+    let names = names();
+
+    let value = Borrowed { name: gluino() };
+    assert_eq!(round_trip(&value, &names), value);
+    assert_eq!(borrow_round_trip(&value, &names), value);
+
+    let value = Elided::Named(gluino());
+    assert_eq!(round_trip(&value, &names), value);
+    assert_eq!(borrow_round_trip(&value, &names), value);
+}
+
+#[test]
+fn test_tuple_context() {
+    let names = names();
+    let units = Units {
+        units: vec!["GeV".to_string()],
+    };
+
+    let value = Tupled {
+        name: gluino(),
+        count: 3,
+    };
+    assert_eq!(round_trip(&value, (&names, &units)), value);
+}
+
+#[test]
+fn test_introduced_generic_context() {
+    let names = names();
+
+    let value = Introduced { name: gluino() };
+    assert_eq!(round_trip(&value, &names), value);
+}
+
+#[test]
+fn test_context_depending_on_struct_generic() {
+    let value = Registered { value: 5u64 };
+    let registry = Registry { default: 0u64 };
+    assert_eq!(round_trip(&value, registry), value);
+
+    let registry = Registry {
+        default: PhantomData::<()>,
+    };
+    let value = Registered { value: PhantomData };
+    assert_eq!(borrow_round_trip(&value, registry), value);
+}
+
+pub struct CliContext {
+    pub names: Names,
+}
+
+impl NameLookup for CliContext {
+    fn name(&self, id: u32) -> Option<&str> {
+        self.names.name(id)
+    }
+}
+
+pub struct Fixture;
+
+impl NameLookup for Fixture {
+    fn name(&self, _id: u32) -> Option<&str> {
+        Some("fixture")
+    }
+}
+
+#[derive(Debug, PartialEq, Encode, Decode, BorrowDecodeFromDecode)]
+#[trait_decode(context_type = [CliContext, Fixture, &'a Names])]
+pub enum Listed {
+    Named { name: Name },
+    Count(u32),
+}
+
+#[test]
+fn test_context_type_list() {
+    let value = Listed::Named { name: gluino() };
+
+    let cli = CliContext { names: names() };
+    assert_eq!(round_trip(&value, cli), value);
+
+    let names = names();
+    assert_eq!(borrow_round_trip(&value, &names), value);
+
+    let decoded = round_trip(&value, Fixture);
+    assert_eq!(
+        decoded,
+        Listed::Named {
+            name: Name {
+                id: 1,
+                name: "fixture".to_string()
+            }
+        }
+    );
+
+    let value = Listed::Count(4);
+    assert_eq!(borrow_round_trip(&value, Fixture), value);
+}
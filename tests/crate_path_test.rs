@@ -0,0 +1,57 @@
+mod reexport {
+    pub use bincode as codec;
+}
+
+mod shadowed {
+    use bincode_trait_derive::{BorrowDecodeFromDecode, Decode, Encode};
+
+    // Shadows the bincode crate for every relative path in this module
+    #[allow(dead_code)]
+    mod bincode {}
+
+    #[derive(Debug, PartialEq, Encode, Decode, BorrowDecodeFromDecode)]
+    pub enum Shape {
+        Circle { radius: u32 },
+        Square(u32),
+        Point,
+    }
+
+    #[derive(Debug, PartialEq, Encode, Decode, BorrowDecodeFromDecode)]
+    #[trait_decode(crate = crate::reexport::codec)]
+    pub struct Drawing<T> {
+        pub shapes: Vec<Shape>,
+        pub label: T,
+    }
+}
+
+use shadowed::{Drawing, Shape};
+
+#[test]
+fn test_reexported_crate_path() {
+    let drawing = Drawing {
+        shapes: vec![Shape::Circle { radius: 2 }, Shape::Square(3), Shape::Point],
+        label: "shapes".to_string(),
+    };
+
+    let encoded: Vec<u8> =
+        reexport::codec::encode_to_vec(&drawing, reexport::codec::config::standard()).unwrap();
+    let (decoded, _): (Drawing<String>, usize) =
+        reexport::codec::decode_from_slice(&encoded, reexport::codec::config::standard()).unwrap();
+    assert_eq!(decoded, drawing);
+
+    let (borrowed, _): (Drawing<String>, usize) =
+        reexport::codec::borrow_decode_from_slice(&encoded, reexport::codec::config::standard())
+            .unwrap();
+    assert_eq!(borrowed, drawing);
+}
+
+#[test]
+fn test_unexpected_variant_in_shadowing_module() {
+    let encoded: Vec<u8> = bincode::encode_to_vec(7usize, bincode::config::standard()).unwrap();
+    let result: Result<(Shape, usize), _> =
+        bincode::decode_from_slice(&encoded, bincode::config::standard());
+    assert!(matches!(
+        result,
+        Err(bincode::error::DecodeError::UnexpectedVariant { found: 7, .. })
+    ));
+}
@@ -0,0 +1,94 @@
+use bincode::error::{AllowedEnumVariants, DecodeError};
+use bincode_trait_derive::{BorrowDecodeFromDecode, Decode, Encode};
+
+// `Removed` used to be written as 1, and `Circle` was added after `Square`
+#[derive(Debug, PartialEq, Encode, Decode, BorrowDecodeFromDecode)]
+#[repr(u8)]
+pub enum Shape {
+    Point = 0,
+    Square(u32) = 2,
+    Circle(u32),
+    #[trait_decode(index = 10)]
+    Polygon(Vec<(i32, i32)>),
+}
+
+#[derive(Debug, PartialEq, Encode, Decode, BorrowDecodeFromDecode)]
+#[trait_decode(stable_indices)]
+pub enum Message {
+    #[trait_decode(index = 3)]
+    Pong,
+    #[trait_decode(skip)]
+    Local(u8),
+    #[trait_decode(index = 1)]
+    Ping(u64),
+}
+
+// The index decides the format, whatever the discriminant of the variant in memory
+#[derive(Debug, Clone, Copy, PartialEq, Encode, Decode, BorrowDecodeFromDecode)]
+#[repr(u8)]
+pub enum Level {
+    #[trait_decode(index = 0)]
+    Low = 10,
+    #[trait_decode(index = 1)]
+    High = 20,
+}
+
+fn encode<T: bincode::Encode>(value: T) -> Vec<u8> {
+    bincode::encode_to_vec(value, bincode::config::standard()).unwrap()
+}
+
+#[test]
+fn test_explicit_discriminants() {
+    assert_eq!(encode(Shape::Point), encode(0usize));
+    assert_eq!(encode(Shape::Square(4)), encode((2usize, 4u32)));
+    assert_eq!(encode(Shape::Circle(4)), encode((3usize, 4u32)));
+    assert_eq!(
+        encode(Shape::Polygon(vec![(0, 1)])),
+        encode((10usize, vec![(0i32, 1i32)]))
+    );
+
+    for shape in [
+        Shape::Point,
+        Shape::Square(1),
+        Shape::Circle(2),
+        Shape::Polygon(vec![(1, 2), (3, 4)]),
+    ] {
+        let (decoded, _): (Shape, usize) =
+            bincode::borrow_decode_from_slice(&encode(&shape), bincode::config::standard())
+                .unwrap();
+        assert_eq!(decoded, shape);
+    }
+}
+
+#[test]
+fn test_unexpected_variant_lists_indices() {
+    let err = bincode::decode_from_slice::<Shape, _>(&encode(1usize), bincode::config::standard())
+        .unwrap_err();
+    match err {
+        DecodeError::UnexpectedVariant {
+            type_name,
+            found,
+            allowed: AllowedEnumVariants::Allowed(allowed),
+        } => {
+            assert_eq!(type_name, "Shape");
+            assert_eq!(found, 1);
+            assert_eq!(*allowed, [0, 2, 3, 10]);
+        }
+        err => panic!("unexpected error {err:?}"),
+    }
+}
+
+#[test]
+fn test_stable_indices() {
+    assert_eq!(encode(Message::Ping(5)), encode((1usize, 5u64)));
+    assert_eq!(encode(Message::Pong), encode(3usize));
+
+    let (decoded, _): (Message, usize) =
+        bincode::decode_from_slice(&encode((1usize, 5u64)), bincode::config::standard()).unwrap();
+    assert_eq!(decoded, Message::Ping(5));
+
+    assert_eq!(encode(Level::High), encode(1usize));
+    let (decoded, _): (Level, usize) =
+        bincode::decode_from_slice(&encode(0usize), bincode::config::standard()).unwrap();
+    assert_eq!(decoded, Level::Low);
+}
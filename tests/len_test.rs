@@ -0,0 +1,181 @@
+use bincode::error::{DecodeError, EncodeError};
+use bincode_trait_derive::{BorrowDecodeFromDecode, Decode, Encode};
+
+#[derive(Debug, PartialEq, Encode, Decode, BorrowDecodeFromDecode)]
+pub struct Polynomial<E> {
+    pub nvars: usize,
+    pub coefficients: Vec<i64>,
+    #[trait_decode(len = coefficients.len() * nvars)]
+    pub exponents: Vec<E>,
+}
+
+pub struct Variables {
+    pub names: Vec<String>,
+}
+
+impl Variables {
+    fn count(&self) -> usize {
+        self.names.len()
+    }
+}
+
+#[derive(Debug, PartialEq, Encode, Decode, BorrowDecodeFromDecode)]
+#[trait_decode(context_type = Variables)]
+pub struct Point {
+    #[trait_decode(len_with = Variables::count)]
+    pub coordinates: Box<[f64]>,
+}
+
+#[derive(Debug, PartialEq, Encode, Decode, BorrowDecodeFromDecode)]
+pub enum Code {
+    Fixed(u8, #[trait_decode(len = 4)] String),
+    Sized {
+        len: u32,
+        #[trait_decode(len = *len as usize)]
+        value: String,
+    },
+}
+
+// Fields named like the parameters and locals of the generated code do not shadow them
+#[derive(Debug, PartialEq, Encode, Decode, BorrowDecodeFromDecode)]
+pub struct Frame {
+    pub encoder: u8,
+    pub len: usize,
+    #[trait_decode(len = *len)]
+    pub value: Vec<u8>,
+    #[trait_decode(len = value.len())]
+    pub item: String,
+}
+
+fn encode<T: bincode::Encode>(value: T) -> Vec<u8> {
+    bincode::encode_to_vec(value, bincode::config::standard()).unwrap()
+}
+
+fn polynomial() -> Polynomial<u16> {
+    Polynomial {
+        nvars: 2,
+        coefficients: vec![3, -1],
+        exponents: vec![1, 0, 0, 2],
+    }
+}
+
+#[test]
+fn test_len_from_earlier_fields() {
+    let polynomial = polynomial();
+    let encoded = encode(&polynomial);
+    // The exponents follow the coefficients without a length prefix
+    assert_eq!(
+        encoded,
+        [
+            encode(2usize),
+            encode(vec![3i64, -1]),
+            encode((1u16, 0u16, 0u16, 2u16))
+        ]
+        .concat()
+    );
+
+    let (decoded, _): (Polynomial<u16>, usize) =
+        bincode::borrow_decode_from_slice(&encoded, bincode::config::standard()).unwrap();
+    assert_eq!(decoded, polynomial);
+}
+
+#[test]
+fn test_len_is_checked_when_encoding() {
+    let mut polynomial = polynomial();
+    polynomial.exponents.pop();
+    let err = bincode::encode_to_vec(&polynomial, bincode::config::standard()).unwrap_err();
+    match err {
+        EncodeError::OtherString(message) => {
+            assert_eq!(
+                message,
+                "`Polynomial.exponents` has length 3 but `len` is 4"
+            )
+        }
+        err => panic!("unexpected error {err:?}"),
+    }
+}
+
+#[test]
+fn test_len_from_context() {
+    let point = Point {
+        coordinates: Box::new([0.5, -1.0]),
+    };
+    let encoded = encode(&point);
+    assert_eq!(encoded, encode((0.5f64, -1.0f64)));
+
+    let variables = Variables {
+        names: vec!["x".to_string(), "y".to_string()],
+    };
+    let (decoded, _): (Point, usize) =
+        bincode::decode_from_slice_with_context(&encoded, bincode::config::standard(), variables)
+            .unwrap();
+    assert_eq!(decoded, point);
+}
+
+#[test]
+fn test_len_from_context_is_not_checked_when_encoding() {
+    // Three coordinates are written for a context with two variables
+    let point = Point {
+        coordinates: Box::new([0.5, -1.0, 2.0]),
+    };
+    let encoded = encode(&point);
+    assert_eq!(encoded, encode((0.5f64, -1.0f64, 2.0f64)));
+
+    let variables = Variables {
+        names: vec!["x".to_string(), "y".to_string()],
+    };
+    let (decoded, read): (Point, usize) =
+        bincode::decode_from_slice_with_context(&encoded, bincode::config::standard(), variables)
+            .unwrap();
+    // The last coordinate is left in the stream, for whatever is decoded next
+    assert_eq!(&*decoded.coordinates, [0.5, -1.0]);
+    assert!(read < encoded.len());
+}
+
+#[test]
+fn test_len_of_strings() {
+    for code in [
+        Code::Fixed(1, "abcd".to_string()),
+        Code::Sized {
+            len: 3,
+            value: "xyz".to_string(),
+        },
+    ] {
+        let encoded = encode(&code);
+        let (decoded, _): (Code, usize) =
+            bincode::decode_from_slice(&encoded, bincode::config::standard()).unwrap();
+        assert_eq!(decoded, code);
+    }
+    assert_eq!(
+        encode(Code::Fixed(1, "abcd".to_string())),
+        [0, 1, b'a', b'b', b'c', b'd']
+    );
+
+    let err =
+        bincode::decode_from_slice::<Code, _>(&[0, 1, 0xff, 0, 0, 0], bincode::config::standard())
+            .unwrap_err();
+    assert!(matches!(err, DecodeError::Utf8 { .. }));
+}
+
+#[test]
+fn test_len_respects_limit() {
+    let config = bincode::config::standard().with_limit::<16>();
+    let encoded = encode((1000usize, vec![1i64]));
+    let err = bincode::decode_from_slice::<Polynomial<u64>, _>(&encoded, config).unwrap_err();
+    assert!(matches!(err, DecodeError::LimitExceeded));
+}
+
+#[test]
+fn test_fields_named_like_generated_locals() {
+    let frame = Frame {
+        encoder: 1,
+        len: 2,
+        value: vec![3, 4],
+        item: "ab".to_string(),
+    };
+    let encoded = encode(&frame);
+    assert_eq!(encoded, [1, 2, 3, 4, b'a', b'b']);
+    let (decoded, _): (Frame, usize) =
+        bincode::decode_from_slice(&encoded, bincode::config::standard()).unwrap();
+    assert_eq!(decoded, frame);
+}
@@ -0,0 +1,173 @@
+use bincode::error::DecodeError;
+use bincode_trait_derive::{BorrowDecodeFromDecode, Decode, Encode};
+
+// Neither Encode nor Decode: only written as its id
+#[derive(Debug, Clone, PartialEq)]
+pub struct Particle {
+    pub id: isize,
+    pub name: String,
+}
+
+pub struct ParticleList {
+    pub particles: Vec<Particle>,
+}
+
+impl ParticleList {
+    fn get_particle_from_id(&self, id: isize) -> Option<Particle> {
+        self.particles.iter().find(|p| p.id == id).cloned()
+    }
+}
+
+pub trait ParticleListTrait {
+    fn get_particle_list(&self) -> &ParticleList;
+}
+
+impl ParticleListTrait for ParticleList {
+    fn get_particle_list(&self) -> &ParticleList {
+        self
+    }
+}
+
+pub struct Model {
+    pub particles: ParticleList,
+}
+
+impl ParticleListTrait for Model {
+    fn get_particle_list(&self) -> &ParticleList {
+        &self.particles
+    }
+}
+
+fn particle_id(particle: &Particle) -> isize {
+    particle.id
+}
+
+#[derive(Debug, PartialEq, Encode, Decode, BorrowDecodeFromDecode)]
+#[trait_decode(trait = ParticleListTrait)]
+pub struct Vertex {
+    #[trait_decode(lookup(
+        key = isize,
+        encode = |p| p.id,
+        resolve = ParticleListTrait::get_particle_list,
+        by = get_particle_from_id,
+    ))]
+    pub incoming: Particle,
+    #[trait_decode(lookup(
+        key = isize,
+        encode = particle_id,
+        resolve = ParticleListTrait::get_particle_list,
+        by = get_particle_from_id,
+    ))]
+    pub outgoing: Particle,
+    pub coupling: u32,
+}
+
+// Without `resolve`, the key is looked up on the context itself
+#[derive(Debug, PartialEq, Encode, Decode, BorrowDecodeFromDecode)]
+#[trait_decode(context_type = ParticleList)]
+pub enum Propagator {
+    Massless,
+    Massive(
+        #[trait_decode(lookup(key = isize, encode = |p| p.id, by = get_particle_from_id))] Particle,
+        u32,
+    ),
+}
+
+fn model() -> Model {
+    Model {
+        particles: particles(),
+    }
+}
+
+fn particles() -> ParticleList {
+    ParticleList {
+        particles: vec![
+            Particle {
+                id: 1,
+                name: "electron".to_string(),
+            },
+            Particle {
+                id: 22,
+                name: "photon".to_string(),
+            },
+        ],
+    }
+}
+
+#[test]
+fn test_lookup_round_trip() {
+    let particles = particles();
+    let vertex = Vertex {
+        incoming: particles.particles[0].clone(),
+        outgoing: particles.particles[1].clone(),
+        coupling: 3,
+    };
+
+    let encoded = bincode::encode_to_vec(&vertex, bincode::config::standard()).unwrap();
+    assert_eq!(
+        encoded,
+        bincode::encode_to_vec((1isize, 22isize, 3u32), bincode::config::standard()).unwrap()
+    );
+
+    let (decoded, _): (Vertex, usize) =
+        bincode::decode_from_slice_with_context(&encoded, bincode::config::standard(), model())
+            .unwrap();
+    assert_eq!(decoded, vertex);
+
+    let (decoded, _): (Vertex, usize) = bincode::borrow_decode_from_slice_with_context(
+        &encoded,
+        bincode::config::standard(),
+        particles,
+    )
+    .unwrap();
+    assert_eq!(decoded, vertex);
+}
+
+#[test]
+fn test_lookup_on_context() {
+    let propagator = Propagator::Massive(particles().particles[1].clone(), 0);
+    let encoded = bincode::encode_to_vec(&propagator, bincode::config::standard()).unwrap();
+    let (decoded, _): (Propagator, usize) =
+        bincode::decode_from_slice_with_context(&encoded, bincode::config::standard(), particles())
+            .unwrap();
+    assert_eq!(decoded, propagator);
+}
+
+#[test]
+fn test_missing_key_is_an_error() {
+    let encoded =
+        bincode::encode_to_vec((1isize, 5isize, 3u32), bincode::config::standard()).unwrap();
+    let err = bincode::decode_from_slice_with_context::<_, Vertex, _>(
+        &encoded,
+        bincode::config::standard(),
+        model(),
+    )
+    .unwrap_err();
+    match err {
+        DecodeError::OtherString(message) => {
+            assert_eq!(
+                message,
+                "cannot resolve `Vertex.outgoing`: no entry for key 5"
+            )
+        }
+        err => panic!("unexpected error {err:?}"),
+    }
+
+    let encoded =
+        bincode::encode_to_vec((1usize, 7isize, 0u32), bincode::config::standard()).unwrap();
+    let err = bincode::decode_from_slice_with_context::<_, Propagator, _>(
+        &encoded,
+        bincode::config::standard(),
+        particles(),
+    )
+    .unwrap_err();
+    match err {
+        DecodeError::OtherString(message) => {
+            assert_eq!(
+                message,
+                "cannot resolve `Propagator::Massive.0`: no entry for key 7"
+            )
+        }
+        err => panic!("unexpected error {err:?}"),
+    }
+}
@@ -13,13 +13,6 @@ pub trait MonomialOrder {}
 pub struct LexOrder {}
 impl MonomialOrder for LexOrder {}
 
-// Implement Encode for LexOrder
-impl bincode::Encode for LexOrder {
-    fn encode<E: bincode::enc::Encoder>(&self, _encoder: &mut E) -> Result<(), bincode::error::EncodeError> {
-        Ok(())
-    }
-}
-
 // Test Ring implementation 
 pub struct TestRing {}
 impl Ring for TestRing {
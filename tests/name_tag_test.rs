@@ -0,0 +1,63 @@
+use bincode::error::DecodeError;
+use bincode_trait_derive::{BorrowDecodeFromDecode, Decode, Encode};
+
+#[derive(Debug, PartialEq, Encode, Decode, BorrowDecodeFromDecode)]
+#[trait_decode(tag = "name")]
+pub enum Unit {
+    Metre,
+    #[trait_decode(rename = "s", alias = "sec", alias = "Second")]
+    Second,
+    #[trait_decode(skip)]
+    Scratch,
+    Scaled {
+        factor: f64,
+        unit: Box<Unit>,
+    },
+}
+
+fn encode<T: bincode::Encode>(value: T) -> Vec<u8> {
+    bincode::encode_to_vec(value, bincode::config::standard()).unwrap()
+}
+
+#[test]
+fn test_encode_by_name() {
+    assert_eq!(encode(Unit::Metre), encode("Metre"));
+    assert_eq!(encode(Unit::Second), encode("s"));
+
+    let unit = Unit::Scaled {
+        factor: 1e3,
+        unit: Box::new(Unit::Metre),
+    };
+    let encoded = encode(&unit);
+    assert_eq!(encoded, encode(("Scaled", 1e3f64, "Metre")));
+
+    let (decoded, _): (Unit, usize) =
+        bincode::borrow_decode_from_slice(&encoded, bincode::config::standard()).unwrap();
+    assert_eq!(decoded, unit);
+}
+
+#[test]
+fn test_decode_aliases() {
+    for name in ["s", "sec", "Second"] {
+        let (decoded, _): (Unit, usize) =
+            bincode::decode_from_slice(&encode(name), bincode::config::standard()).unwrap();
+        assert_eq!(decoded, Unit::Second);
+    }
+}
+
+#[test]
+fn test_unknown_name() {
+    for name in ["Gram", "Scratch"] {
+        let err = bincode::decode_from_slice::<Unit, _>(&encode(name), bincode::config::standard())
+            .unwrap_err();
+        match err {
+            DecodeError::OtherString(message) => assert_eq!(
+                message,
+                format!(
+                    "unknown variant {name:?} of `Unit`, expected one of `Metre`, `s`, `Scaled`"
+                )
+            ),
+            err => panic!("unexpected error {err:?}"),
+        }
+    }
+}
@@ -0,0 +1,54 @@
+use bincode_trait_derive::{BorrowDecodeFromDecode, Decode, Encode};
+
+pub trait Ring {
+    type Element;
+}
+
+// Neither Encode nor Decode: only its element type is ever written
+pub struct IntegerRing;
+
+impl Ring for IntegerRing {
+    type Element = i64;
+}
+
+pub trait Exponent {}
+impl Exponent for u16 {}
+
+#[derive(Encode, Decode, BorrowDecodeFromDecode)]
+// bincode only decodes `Box<[T]>` for `T: 'static`
+#[trait_decode(add_bound(decode = "F::Element: 'static"))]
+pub struct Projections<F: Ring, E: Exponent> {
+    pub nested: Option<Vec<F::Element>>,
+    pub tuple: (F::Element, E),
+    pub array: [F::Element; 2],
+    pub boxed: Box<[F::Element]>,
+    pub qualified: <F as Ring>::Element,
+    pub name: std::string::String,
+}
+
+#[test]
+fn test_nested_projections() {
+    let value = Projections::<IntegerRing, u16> {
+        nested: Some(vec![1, 2]),
+        tuple: (3, 4),
+        array: [5, 6],
+        boxed: vec![7, 8].into_boxed_slice(),
+        qualified: 9,
+        name: "projections".to_string(),
+    };
+
+    let encoded: Vec<u8> = bincode::encode_to_vec(&value, bincode::config::standard()).unwrap();
+    let (decoded, _): (Projections<IntegerRing, u16>, usize) =
+        bincode::decode_from_slice(&encoded, bincode::config::standard()).unwrap();
+
+    assert_eq!(decoded.nested, Some(vec![1, 2]));
+    assert_eq!(decoded.tuple, (3, 4));
+    assert_eq!(decoded.array, [5, 6]);
+    assert_eq!(&*decoded.boxed, &[7, 8]);
+    assert_eq!(decoded.qualified, 9);
+    assert_eq!(decoded.name, "projections");
+
+    let (borrowed, _): (Projections<IntegerRing, u16>, usize) =
+        bincode::borrow_decode_from_slice(&encoded, bincode::config::standard()).unwrap();
+    assert_eq!(borrowed.qualified, 9);
+}
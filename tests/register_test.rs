@@ -0,0 +1,106 @@
+use bincode::error::DecodeError;
+use bincode_trait_derive::{BorrowDecodeFromDecode, Decode, Encode};
+
+#[derive(Debug, Clone, PartialEq, Encode, Decode, BorrowDecodeFromDecode)]
+pub struct Species {
+    pub id: u32,
+    pub name: String,
+}
+
+#[derive(Default)]
+pub struct FishList {
+    pub species: Vec<Species>,
+}
+
+impl FishList {
+    fn fish(&self, id: u32) -> Option<Fish> {
+        let species = self.species.iter().find(|s| s.id == id)?;
+        Some(Fish {
+            id,
+            name: species.name.clone(),
+        })
+    }
+}
+
+pub trait Aquarium {
+    fn fish_list(&self) -> &FishList;
+
+    fn register_species(&mut self, species: &[Species]);
+}
+
+impl Aquarium for FishList {
+    fn fish_list(&self) -> &FishList {
+        self
+    }
+
+    fn register_species(&mut self, species: &[Species]) {
+        self.species.extend_from_slice(species);
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Encode, Decode, BorrowDecodeFromDecode)]
+#[trait_decode(by_key = id, registry = Aquarium::fish_list, find = fish)]
+pub struct Fish {
+    pub id: u32,
+    pub name: String,
+}
+
+// The species are written inline, and the catches refer to them by id
+#[derive(Debug, PartialEq, Encode, Decode, BorrowDecodeFromDecode)]
+#[trait_decode(infer)]
+pub struct Pond {
+    #[trait_decode(register = Aquarium::register_species)]
+    pub species: Vec<Species>,
+    pub catches: Vec<Fish>,
+}
+
+fn encode<T: bincode::Encode>(value: T) -> Vec<u8> {
+    bincode::encode_to_vec(value, bincode::config::standard()).unwrap()
+}
+
+fn species(id: u32, name: &str) -> Species {
+    Species {
+        id,
+        name: name.to_string(),
+    }
+}
+
+fn fish(id: u32, name: &str) -> Fish {
+    Fish {
+        id,
+        name: name.to_string(),
+    }
+}
+
+#[test]
+fn test_register_inline_definitions() {
+    let pond = Pond {
+        species: vec![species(1, "carp"), species(2, "pike")],
+        catches: vec![fish(2, "pike"), fish(1, "carp"), fish(2, "pike")],
+    };
+    let encoded = encode(&pond);
+    assert_eq!(
+        encoded,
+        encode((vec![(1u32, "carp"), (2u32, "pike")], vec![2u32, 1, 2]))
+    );
+
+    let (decoded, _): (Pond, usize) = bincode::borrow_decode_from_slice_with_context(
+        &encoded,
+        bincode::config::standard(),
+        FishList::default(),
+    )
+    .unwrap();
+    assert_eq!(decoded, pond);
+}
+
+#[test]
+fn test_unregistered_id() {
+    let encoded = encode((vec![(1u32, "carp")], vec![3u32]));
+    let err = bincode::decode_from_slice_with_context::<_, Pond, _>(
+        &encoded,
+        bincode::config::standard(),
+        FishList::default(),
+    )
+    .unwrap_err();
+    assert!(matches!(err, DecodeError::OtherString(_)));
+}
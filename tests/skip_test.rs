@@ -0,0 +1,163 @@
+use std::marker::PhantomData;
+
+use bincode_trait_derive::{BorrowDecodeFromDecode, Decode, Encode};
+
+pub struct Units {
+    pub default_unit: String,
+}
+
+fn default_unit(context: &Units) -> String {
+    context.default_unit.clone()
+}
+
+#[derive(Debug, PartialEq, Encode, Decode, BorrowDecodeFromDecode)]
+#[trait_decode(context_type = Units)]
+pub struct Measurement {
+    pub values: Vec<u32>,
+    #[trait_decode(skip)]
+    pub cache: Option<u32>,
+    #[trait_decode(default = values.iter().sum())]
+    pub total: u32,
+    #[trait_decode(default_with = default_unit)]
+    pub unit: String,
+}
+
+#[derive(Debug, PartialEq, Encode, Decode, BorrowDecodeFromDecode)]
+pub struct Pair(
+    pub u32,
+    #[trait_decode(default = field0 * 2)] pub u32,
+    #[trait_decode(skip)] pub String,
+);
+
+// Not Default, Encode or Decode: only appears in skipped fields
+pub struct Handle;
+
+#[derive(Debug, PartialEq, Encode, Decode, BorrowDecodeFromDecode)]
+pub struct Tagged<T, H> {
+    pub value: u32,
+    #[trait_decode(skip)]
+    pub label: T,
+    #[trait_decode(skip)]
+    pub _handle: PhantomData<H>,
+}
+
+#[derive(Debug, PartialEq, Encode, Decode, BorrowDecodeFromDecode)]
+pub enum Shape {
+    Circle(u32),
+    #[trait_decode(skip)]
+    Cached {
+        handle: u64,
+    },
+    Square {
+        side: u32,
+        #[trait_decode(default = side * side)]
+        area: u32,
+    },
+}
+
+// Fields named like the parameters of the generated code do not shadow them
+#[derive(Debug, PartialEq, Encode, Decode, BorrowDecodeFromDecode)]
+pub struct Named {
+    pub decoder: u32,
+    pub value: u32,
+    #[trait_decode(default = decoder + value)]
+    pub sum: u32,
+}
+
+fn encode<T: bincode::Encode>(value: &T) -> Vec<u8> {
+    bincode::encode_to_vec(value, bincode::config::standard()).unwrap()
+}
+
+#[test]
+fn test_skipped_fields_are_not_encoded() {
+    let measurement = Measurement {
+        values: vec![1, 2, 3],
+        cache: Some(7),
+        total: 0,
+        unit: "m".to_string(),
+    };
+    let encoded = encode(&measurement);
+    assert_eq!(encoded, encode(&vec![1u32, 2, 3]));
+
+    let units = Units {
+        default_unit: "GeV".to_string(),
+    };
+    let (decoded, _): (Measurement, usize) =
+        bincode::decode_from_slice_with_context(&encoded, bincode::config::standard(), units)
+            .unwrap();
+    assert_eq!(
+        decoded,
+        Measurement {
+            values: vec![1, 2, 3],
+            cache: None,
+            total: 6,
+            unit: "GeV".to_string(),
+        }
+    );
+}
+
+#[test]
+fn test_default_refers_to_tuple_fields() {
+    let pair = Pair(21, 0, "cached".to_string());
+    let encoded = encode(&pair);
+    assert_eq!(encoded, encode(&21u32));
+
+    let (decoded, _): (Pair, usize) =
+        bincode::decode_from_slice(&encoded, bincode::config::standard()).unwrap();
+    assert_eq!(decoded, Pair(21, 42, String::new()));
+}
+
+#[test]
+fn test_skipped_generic_fields() {
+    let tagged = Tagged::<String, Handle> {
+        value: 3,
+        label: "label".to_string(),
+        _handle: PhantomData,
+    };
+    let encoded = encode(&tagged);
+
+    let (decoded, _): (Tagged<String, Handle>, usize) =
+        bincode::borrow_decode_from_slice(&encoded, bincode::config::standard()).unwrap();
+    assert_eq!(decoded.value, 3);
+    assert_eq!(decoded.label, "");
+}
+
+#[test]
+fn test_skipped_variant() {
+    // `Square` takes the discriminant after `Circle`
+    assert_eq!(encode(&Shape::Circle(5)), encode(&(0usize, 5u32)));
+    assert_eq!(
+        encode(&Shape::Square { side: 4, area: 0 }),
+        encode(&(1usize, 4u32))
+    );
+
+    let (decoded, _): (Shape, usize) =
+        bincode::decode_from_slice(&encode(&(1usize, 4u32)), bincode::config::standard()).unwrap();
+    assert_eq!(decoded, Shape::Square { side: 4, area: 16 });
+
+    let err = bincode::encode_to_vec(Shape::Cached { handle: 1 }, bincode::config::standard())
+        .unwrap_err();
+    assert!(matches!(
+        err,
+        bincode::error::EncodeError::Other("cannot encode skipped variant Shape::Cached")
+    ));
+
+    let err = bincode::decode_from_slice::<Shape, _>(&encode(&2usize), bincode::config::standard())
+        .unwrap_err();
+    assert!(matches!(
+        err,
+        bincode::error::DecodeError::UnexpectedVariant { found: 2, .. }
+    ));
+}
+
+#[test]
+fn test_fields_named_like_generated_locals() {
+    let named = Named {
+        decoder: 1,
+        value: 2,
+        sum: 3,
+    };
+    let (decoded, _): (Named, usize) =
+        bincode::decode_from_slice(&encode(&named), bincode::config::standard()).unwrap();
+    assert_eq!(decoded, named);
+}
@@ -1,8 +1,10 @@
 use std::marker::PhantomData;
+use std::sync::Arc;
 
 use bincode_trait_derive::{BorrowDecodeFromDecode, Decode, Encode};
 
-#[derive(Clone)]
+#[derive(Clone, Encode, Decode)]
+#[trait_decode(by_key = id, registry = ParticleListTrait::get_particle_list, find = get_particle_from_id)]
 pub struct Particle {
     pub id: isize,
     pub name: String,
@@ -28,46 +30,13 @@ impl ParticleListTrait for ParticleList {
     }
 }
 
-impl bincode::Encode for Particle {
-    fn encode<E: bincode::enc::Encoder>(
-        &self,
-        encoder: &mut E,
-    ) -> Result<(), bincode::error::EncodeError> {
-        self.id.encode(encoder)?;
-        Ok(())
-    }
-}
-
-impl<C> bincode::Decode<C> for Particle
-where
-    C: ParticleListTrait,
-{
-    fn decode<D: bincode::de::Decoder<Context = C>>(
-        decoder: &mut D,
-    ) -> Result<Self, bincode::error::DecodeError> {
-        let id = isize::decode(decoder)?;
-        let context = decoder.context();
-        let particle_list = context.get_particle_list();
-        Ok(particle_list.get_particle_from_id(id).unwrap())
-    }
-}
-
-#[derive(Clone)]
+#[derive(Clone, Encode, Decode)]
+#[trait_decode(by_key = id, registry = FishListTrait::get_fish_list, find = get_fish_from_id)]
 pub struct Fish {
     pub id: isize,
     pub name: String,
 }
 
-impl bincode::Encode for Fish {
-    fn encode<E: bincode::enc::Encoder>(
-        &self,
-        encoder: &mut E,
-    ) -> Result<(), bincode::error::EncodeError> {
-        self.id.encode(encoder)?;
-        Ok(())
-    }
-}
-
 pub struct FishList {
     pub fishes: Vec<Fish>,
 }
@@ -88,28 +57,12 @@ impl FishListTrait for FishList {
     }
 }
 
-impl<C> bincode::Decode<C> for Fish
-where
-    C: FishListTrait,
-{
-    fn decode<D: bincode::de::Decoder<Context = C>>(
-        decoder: &mut D,
-    ) -> Result<Self, bincode::error::DecodeError> {
-        let id = isize::decode(decoder)?;
-        let context = decoder.context();
-        let fish_list = context.get_fish_list();
-        Ok(fish_list.get_fish_from_id(id).unwrap())
-    }
-}
-
 #[derive(Encode, bincode_trait_derive::Decode)]
 #[trait_decode()]
 pub struct Cow {
     pub id: usize,
 }
 
-pub trait ParticleFishTrait: FishListTrait + ParticleListTrait {}
-
 pub struct MyContext {
     pub particle_list: ParticleList,
     pub fish_list: FishList,
@@ -127,10 +80,8 @@ impl FishListTrait for MyContext {
     }
 }
 
-impl ParticleFishTrait for MyContext {}
-
 #[derive(Encode, Decode, BorrowDecodeFromDecode)]
-#[trait_decode(trait = ParticleFishTrait)]
+#[trait_decode(infer)]
 pub struct Test {
     pub particle: Particle,
     pub fish: Fish,
@@ -145,14 +96,37 @@ pub enum FishOrCow {
     Cow(Cow),
 }
 
+#[derive(Encode, Decode, BorrowDecodeFromDecode)]
+#[trait_decode(infer)]
+pub enum ParticleOrFish<T = Cow> {
+    Particle(Particle),
+    Fish { fish: Fish },
+    Other(T),
+}
+
 #[derive(bincode_trait_derive::Encode, Decode, BorrowDecodeFromDecode)]
-#[trait_decode(trait = ParticleFishTrait)]
+#[trait_decode(trait = FishListTrait + ParticleListTrait)]
 pub struct TestGeneric<T = Fish> {
     pub particle: Particle,
     pub fish: Fish,
     pub generic: T,
 }
 
+#[derive(Encode, Decode, BorrowDecodeFromDecode)]
+#[trait_decode(context_param = C)]
+pub struct Model<C: ParticleListTrait> {
+    pub particle: Particle,
+    pub _context: PhantomData<C>,
+}
+
+#[derive(Encode, Decode, BorrowDecodeFromDecode)]
+#[trait_decode(context_param = C, trait = FishListTrait)]
+pub struct Aquarium<C: ParticleListTrait> {
+    pub particle: Particle,
+    pub fish: Fish,
+    pub _context: PhantomData<C>,
+}
+
 pub trait Ring {
     type Element;
 }
@@ -165,17 +139,30 @@ pub trait MonomialOrder {}
 impl MonomialOrder for LexOrder {}
 pub struct LexOrder {}
 
-#[derive(bincode_trait_derive::Encode)]
+pub struct Variable {
+    pub name: String,
+}
+
+/// The session state polynomials are decoded in.
+pub trait PolynomialContext<F> {
+    fn ring(&self) -> F;
+    fn variables(&self) -> Arc<Vec<Variable>>;
+}
+
+#[derive(bincode_trait_derive::Encode, Decode, BorrowDecodeFromDecode)]
 pub struct MultivariatePolynomial<F: Ring, E: Exponent = u16, O: MonomialOrder = LexOrder> {
     pub coefficients: Vec<F::Element>,
     pub exponents: Vec<E>,
     /// The coefficient ring.
+    #[trait_decode(from_context = PolynomialContext::<F>::ring)]
     pub ring: F,
-    // pub variables: Arc<Vec<Variable>>,
+    #[trait_decode(from_context = PolynomialContext::<F>::variables)]
+    pub variables: Arc<Vec<Variable>>,
     pub(crate) _phantom: PhantomData<O>,
 }
 
-#[derive(bincode_trait_derive::Encode)]
+#[derive(bincode_trait_derive::Encode, Decode, BorrowDecodeFromDecode)]
+#[trait_decode(trait = FishListTrait, trait = ParticleListTrait)]
 pub struct TestAssociatedGeneric<T, F: Ring> {
     pub particle: Particle,
     pub fish: Fish,
@@ -215,6 +202,25 @@ pub enum ItemVariantWithSpecificContext {
     ReferencedCow(Cow), // Add a variant with Cow
 }
 
+/// Written like `BincodeDeriveShape`, which uses bincode's own derive. Both number the variants
+/// by position and ignore the Rust discriminants.
+#[derive(Debug, PartialEq, Encode, Decode, BorrowDecodeFromDecode)]
+#[trait_decode(compat = "bincode_derive")]
+#[repr(u8)]
+pub enum Shape {
+    Point = 7,
+    Circle { radius: u32 } = 3,
+    Polygon(Vec<(i16, i16)>),
+}
+
+#[derive(Debug, PartialEq, bincode::Encode, bincode::Decode)]
+#[repr(u8)]
+pub enum BincodeDeriveShape {
+    Point = 7,
+    Circle { radius: u32 } = 3,
+    Polygon(Vec<(i16, i16)>),
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -295,6 +301,94 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_inferred_enum() {
+        let context = build_test_context();
+
+        let values = vec![
+            ParticleOrFish::Particle(Particle {
+                id: 2,
+                name: "gluino".to_string(),
+            }),
+            ParticleOrFish::Fish {
+                fish: Fish {
+                    id: 1,
+                    name: "blobfish".to_string(),
+                },
+            },
+            ParticleOrFish::Other(Cow { id: 7 }),
+        ];
+
+        // Encode
+        let encoded: Vec<u8> =
+            bincode::encode_to_vec(&values, bincode::config::standard()).unwrap();
+
+        // Decode
+        let (decoded, _): (Vec<ParticleOrFish>, usize) =
+            bincode::decode_from_slice_with_context(&encoded, bincode::config::standard(), context)
+                .unwrap();
+
+        match &decoded[..] {
+            [
+                ParticleOrFish::Particle(particle),
+                ParticleOrFish::Fish { fish },
+                ParticleOrFish::Other(cow),
+            ] => {
+                assert_eq!(particle.name, "gluino");
+                assert_eq!(fish.name, "blobfish");
+                assert_eq!(cow.id, 7);
+            }
+            _ => panic!("Unexpected variants"),
+        }
+    }
+
+    #[test]
+    fn test_context_param() {
+        let context = build_test_context();
+
+        let model: Model<MyContext> = Model {
+            particle: Particle {
+                id: 2,
+                name: "gluino".to_string(),
+            },
+            _context: PhantomData,
+        };
+
+        // Encode
+        let encoded: Vec<u8> =
+            bincode::encode_to_vec(&model, bincode::config::standard()).unwrap();
+
+        // Decode
+        let (decoded, _): (Model<MyContext>, usize) =
+            bincode::decode_from_slice_with_context(&encoded, bincode::config::standard(), context)
+                .unwrap();
+
+        assert_eq!(decoded.particle.id, 2);
+        assert_eq!(decoded.particle.name, "gluino");
+
+        let aquarium: Aquarium<MyContext> = Aquarium {
+            particle: model.particle,
+            fish: Fish {
+                id: 3,
+                name: "starfish".to_string(),
+            },
+            _context: PhantomData,
+        };
+
+        let encoded: Vec<u8> =
+            bincode::encode_to_vec(&aquarium, bincode::config::standard()).unwrap();
+
+        let (decoded, _): (Aquarium<MyContext>, usize) = bincode::borrow_decode_from_slice_with_context(
+            &encoded,
+            bincode::config::standard(),
+            build_test_context(),
+        )
+        .unwrap();
+
+        assert_eq!(decoded.particle.name, "gluino");
+        assert_eq!(decoded.fish.name, "starfish");
+    }
+
     #[test]
     fn test_generic_cow() {
         let context = build_test_context();
@@ -362,4 +456,92 @@ mod tests {
         assert_eq!(decoded.generic.id, 3);
         assert_eq!(decoded.generic.name, "starfish");
     }
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct IntegerRing;
+
+    impl Ring for IntegerRing {
+        type Element = i64;
+    }
+
+    struct Session {
+        variables: Arc<Vec<Variable>>,
+    }
+
+    impl PolynomialContext<IntegerRing> for Session {
+        fn ring(&self) -> IntegerRing {
+            IntegerRing
+        }
+
+        fn variables(&self) -> Arc<Vec<Variable>> {
+            self.variables.clone()
+        }
+    }
+
+    #[test]
+    fn test_polynomial_from_context() {
+        let session = Session {
+            variables: Arc::new(vec![
+                Variable {
+                    name: "x".to_string(),
+                },
+                Variable {
+                    name: "y".to_string(),
+                },
+            ]),
+        };
+
+        let poly: MultivariatePolynomial<IntegerRing> = MultivariatePolynomial {
+            coefficients: vec![3, -1],
+            exponents: vec![1, 0, 0, 2],
+            ring: IntegerRing,
+            variables: session.variables.clone(),
+            _phantom: PhantomData,
+        };
+
+        // Neither the ring nor the variables are written
+        let encoded: Vec<u8> = bincode::encode_to_vec(&poly, bincode::config::standard()).unwrap();
+        let data: Vec<u8> = bincode::encode_to_vec(
+            (&poly.coefficients, &poly.exponents),
+            bincode::config::standard(),
+        )
+        .unwrap();
+        assert_eq!(encoded, data);
+
+        let (decoded, _): (MultivariatePolynomial<IntegerRing>, usize) =
+            bincode::decode_from_slice_with_context(&encoded, bincode::config::standard(), session)
+                .unwrap();
+        assert_eq!(decoded.coefficients, poly.coefficients);
+        assert_eq!(decoded.exponents, poly.exponents);
+        assert_eq!(decoded.ring, IntegerRing);
+        assert!(Arc::ptr_eq(&decoded.variables, &poly.variables));
+    }
+
+    #[test]
+    fn test_bincode_derive_compat() {
+        let shapes = [
+            (Shape::Point, BincodeDeriveShape::Point),
+            (
+                Shape::Circle { radius: 4 },
+                BincodeDeriveShape::Circle { radius: 4 },
+            ),
+            (
+                Shape::Polygon(vec![(0, 1), (-1, 0)]),
+                BincodeDeriveShape::Polygon(vec![(0, 1), (-1, 0)]),
+            ),
+        ];
+        for (shape, expected) in shapes {
+            let standard = bincode::config::standard();
+            let legacy = bincode::config::legacy();
+            assert_eq!(
+                bincode::encode_to_vec(&shape, standard).unwrap(),
+                bincode::encode_to_vec(&expected, standard).unwrap()
+            );
+            let encoded = bincode::encode_to_vec(&expected, legacy).unwrap();
+            assert_eq!(bincode::encode_to_vec(&shape, legacy).unwrap(), encoded);
+
+            let (decoded, _): (Shape, usize) = bincode::decode_from_slice(&encoded, legacy).unwrap();
+            assert_eq!(decoded, shape);
+        }
+    }
 }
@@ -0,0 +1,75 @@
+use bincode::error::DecodeError;
+use bincode_trait_derive::{BorrowDecodeFromDecode, Decode, Encode};
+
+#[derive(Debug, PartialEq, Encode, Decode, BorrowDecodeFromDecode)]
+#[trait_decode(tag = u8)]
+pub enum Opcode {
+    Nop,
+    Push(i32),
+    #[trait_decode(index = 255)]
+    Halt,
+}
+
+#[derive(Debug, PartialEq, Encode, Decode, BorrowDecodeFromDecode)]
+#[trait_decode(tag = u16)]
+pub enum Token {
+    Word(String),
+    Number(u64),
+}
+
+#[derive(Debug, PartialEq, Encode, Decode, BorrowDecodeFromDecode)]
+#[trait_decode(tag = u64)]
+pub enum Wide {
+    A,
+    B,
+}
+
+fn encode<T: bincode::Encode>(value: T) -> Vec<u8> {
+    bincode::encode_to_vec(value, bincode::config::legacy()).unwrap()
+}
+
+#[test]
+fn test_tag_width() {
+    assert_eq!(encode(Opcode::Nop), [0]);
+    assert_eq!(encode(Opcode::Halt), [255]);
+    assert_eq!(encode(Opcode::Push(-1)), encode((1u8, -1i32)));
+    assert_eq!(encode(Token::Number(3)), encode((1u16, 3u64)));
+
+    for opcode in [Opcode::Nop, Opcode::Push(5), Opcode::Halt] {
+        let (decoded, _): (Opcode, usize) =
+            bincode::borrow_decode_from_slice(&encode(&opcode), bincode::config::legacy()).unwrap();
+        assert_eq!(decoded, opcode);
+    }
+
+    let token = Token::Word("tag".to_string());
+    let (decoded, _): (Token, usize) =
+        bincode::decode_from_slice(&encode(&token), bincode::config::legacy()).unwrap();
+    assert_eq!(decoded, token);
+}
+
+#[test]
+fn test_unknown_tag() {
+    let err = bincode::decode_from_slice::<Opcode, _>(&[2], bincode::config::legacy()).unwrap_err();
+    assert!(matches!(
+        err,
+        DecodeError::UnexpectedVariant {
+            type_name: "Opcode",
+            found: 2,
+            ..
+        }
+    ));
+}
+
+#[test]
+fn test_unknown_tag_wider_than_u32() {
+    let encoded = encode((1u64 << 32) + 1);
+    let err =
+        bincode::decode_from_slice::<Wide, _>(&encoded, bincode::config::legacy()).unwrap_err();
+    match err {
+        DecodeError::OtherString(message) => assert_eq!(
+            message,
+            "unexpected variant index 4294967297 of `Wide`, expected one of [0, 1]"
+        ),
+        err => panic!("unexpected error {err:?}"),
+    }
+}
@@ -0,0 +1,85 @@
+use bincode_trait_derive::{BorrowDecodeFromDecode, Decode, Encode};
+
+pub trait Lookup<'a> {
+    fn lookup(&'a self, id: u32) -> Option<&'a str>;
+}
+
+pub struct NameTable {
+    pub names: Vec<String>,
+}
+
+impl<'a> Lookup<'a> for NameTable {
+    fn lookup(&'a self, id: u32) -> Option<&'a str> {
+        self.names.get(id as usize).map(String::as_str)
+    }
+}
+
+pub trait Versioned {
+    fn version(&self) -> u32;
+}
+
+impl Versioned for NameTable {
+    fn version(&self) -> u32 {
+        1
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Name(pub String);
+
+impl bincode::Encode for Name {
+    fn encode<E: bincode::enc::Encoder>(
+        &self,
+        _encoder: &mut E,
+    ) -> Result<(), bincode::error::EncodeError> {
+        Ok(())
+    }
+}
+
+impl<C> bincode::Decode<C> for Name
+where
+    C: for<'a> Lookup<'a>,
+{
+    fn decode<D: bincode::de::Decoder<Context = C>>(
+        decoder: &mut D,
+    ) -> Result<Self, bincode::error::DecodeError> {
+        let name = decoder
+            .context()
+            .lookup(0)
+            .ok_or(bincode::error::DecodeError::Other("empty name table"))?;
+        Ok(Name(name.to_string()))
+    }
+}
+
+#[derive(Debug, PartialEq, Encode, Decode, BorrowDecodeFromDecode)]
+#[trait_decode(trait = for<'a> Lookup<'a> + Versioned)]
+pub struct Named {
+    pub id: u32,
+    pub name: Name,
+}
+
+#[derive(Debug, PartialEq, Encode, Decode, BorrowDecodeFromDecode)]
+#[trait_decode(trait = for<'a> Lookup<'a>, trait = Versioned + 'static)]
+pub enum MaybeNamed {
+    Named(Named),
+    Anonymous,
+}
+
+#[test]
+fn test_multiple_bounds() {
+    let context = NameTable {
+        names: vec!["first".to_string()],
+    };
+
+    let value = MaybeNamed::Named(Named {
+        id: 3,
+        name: Name("first".to_string()),
+    });
+
+    let encoded: Vec<u8> = bincode::encode_to_vec(&value, bincode::config::standard()).unwrap();
+    let (decoded, _): (MaybeNamed, usize) =
+        bincode::decode_from_slice_with_context(&encoded, bincode::config::standard(), context)
+            .unwrap();
+
+    assert_eq!(decoded, value);
+}
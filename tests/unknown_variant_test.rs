@@ -0,0 +1,178 @@
+use bincode_trait_derive::{BorrowDecodeFromDecode, Decode, Encode};
+
+/// The shapes a newer version writes.
+#[derive(Debug, Clone, PartialEq, Encode, Decode, BorrowDecodeFromDecode)]
+#[trait_decode(length_prefixed)]
+pub enum ShapeV2 {
+    Circle { radius: u32 },
+    Square(u32),
+    Polygon(Vec<(i32, i32)>),
+}
+
+/// The shapes an older version knows about.
+#[derive(Debug, Clone, PartialEq, Encode, Decode, BorrowDecodeFromDecode)]
+#[trait_decode(length_prefixed)]
+pub enum ShapeV1 {
+    Circle {
+        radius: u32,
+    },
+    Square(u32),
+    #[trait_decode(unknown)]
+    Unknown {
+        tag: usize,
+        bytes: Vec<u8>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Encode, Decode, BorrowDecodeFromDecode)]
+#[trait_decode(tag = "name", length_prefixed)]
+pub enum Setting {
+    Volume(u8),
+    #[trait_decode(unknown)]
+    Other(String, Vec<u8>),
+}
+
+#[derive(Debug, PartialEq, Encode, Decode, BorrowDecodeFromDecode)]
+#[trait_decode(length_prefixed)]
+pub enum Expr {
+    Literal(i64),
+    Neg(Box<Expr>),
+}
+
+#[derive(Clone, Default)]
+pub struct Seen {
+    pub names: Vec<String>,
+}
+
+fn remember(seen: &mut Seen, name: &str) {
+    seen.names.push(name.to_string());
+}
+
+#[derive(Debug, PartialEq, Encode, Decode, BorrowDecodeFromDecode)]
+#[trait_decode(context_type = Seen, length_prefixed)]
+pub enum Greeting {
+    Hello(#[trait_decode(register = remember)] String),
+}
+
+fn encode<T: bincode::Encode>(value: T) -> Vec<u8> {
+    bincode::encode_to_vec(value, bincode::config::standard()).unwrap()
+}
+
+fn decode<T: bincode::Decode<()>>(bytes: &[u8]) -> T {
+    let (value, read) = bincode::decode_from_slice(bytes, bincode::config::standard()).unwrap();
+    assert_eq!(read, bytes.len());
+    value
+}
+
+#[test]
+fn test_length_prefixed_layout() {
+    assert_eq!(
+        encode(ShapeV2::Circle { radius: 300 }),
+        encode((0usize, encode(300u32)))
+    );
+    assert_eq!(
+        encode(ShapeV2::Polygon(vec![(1, 2)])),
+        encode((2usize, encode(vec![(1i32, 2i32)])))
+    );
+
+    let shapes = vec![ShapeV2::Square(4), ShapeV2::Polygon(vec![(0, 0), (1, 1)])];
+    assert_eq!(decode::<Vec<ShapeV2>>(&encode(&shapes)), shapes);
+}
+
+#[test]
+fn test_unknown_variant_round_trips() {
+    let newer = vec![
+        ShapeV2::Polygon(vec![(0, 0), (3, 4)]),
+        ShapeV2::Circle { radius: 2 },
+    ];
+    let encoded = encode(&newer);
+
+    // The rest of the stream is still read after the unknown variant
+    let older: Vec<ShapeV1> = decode(&encoded);
+    assert_eq!(
+        older,
+        [
+            ShapeV1::Unknown {
+                tag: 2,
+                bytes: encode(vec![(0i32, 0i32), (3, 4)]),
+            },
+            ShapeV1::Circle { radius: 2 },
+        ]
+    );
+
+    let encoded_again = encode(&older);
+    assert_eq!(encoded_again, encoded);
+    assert_eq!(decode::<Vec<ShapeV2>>(&encoded_again), newer);
+}
+
+#[test]
+fn test_unknown_name() {
+    let encoded = encode(("Balance", encode(-3i8), "Volume", encode(7u8)));
+    let settings: (Setting, Setting) = decode(&encoded);
+    assert_eq!(
+        settings,
+        (
+            Setting::Other("Balance".to_string(), encode(-3i8)),
+            Setting::Volume(7)
+        )
+    );
+    assert_eq!(encode(&settings), encoded);
+}
+
+#[test]
+fn test_unknown_payload_respects_limit() {
+    let encoded = encode((5usize, 1000usize));
+    let config = bincode::config::standard().with_limit::<64>();
+    let err = bincode::decode_from_slice::<ShapeV1, _>(&encoded, config).unwrap_err();
+    assert!(matches!(err, bincode::error::DecodeError::LimitExceeded));
+}
+
+#[test]
+fn test_trailing_payload_bytes_are_skipped() {
+    // A newer version that appended a field to `Circle`
+    let encoded = encode((0usize, encode((2u32, 7u8)), 1usize, encode(3u32)));
+    let shapes: (ShapeV1, ShapeV1) = decode(&encoded);
+    assert_eq!(shapes, (ShapeV1::Circle { radius: 2 }, ShapeV1::Square(3)));
+}
+
+#[test]
+fn test_short_payload() {
+    let encoded = encode((0usize, Vec::<u8>::new(), 1usize, encode(3u32)));
+    let err =
+        bincode::decode_from_slice::<(ShapeV1, ShapeV1), _>(&encoded, bincode::config::standard())
+            .unwrap_err();
+    assert!(matches!(
+        err,
+        bincode::error::DecodeError::UnexpectedEnd { .. }
+    ));
+}
+
+#[test]
+fn test_nested_payloads_are_encoded_once() {
+    let mut expr = Expr::Literal(1);
+    for _ in 0..200 {
+        expr = Expr::Neg(Box::new(expr));
+    }
+    let encoded = encode(&expr);
+    assert_eq!(decode::<Expr>(&encoded), expr);
+}
+
+#[test]
+fn test_payload_keeps_context_changes() {
+    let greetings = vec![
+        Greeting::Hello("ann".to_string()),
+        Greeting::Hello("bo".to_string()),
+    ];
+    let encoded = encode(&greetings);
+    let mut decoder = bincode::de::DecoderImpl::new(
+        bincode::de::read::SliceReader::new(&encoded),
+        bincode::config::standard(),
+        Seen::default(),
+    );
+    let decoded: Vec<Greeting> = bincode::Decode::decode(&mut decoder).unwrap();
+    assert_eq!(decoded, greetings);
+    assert_eq!(
+        bincode::de::Decoder::context(&mut decoder).names,
+        ["ann", "bo"]
+    );
+}
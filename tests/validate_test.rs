@@ -0,0 +1,124 @@
+use std::cell::Cell;
+
+use bincode::error::{DecodeError, EncodeError};
+use bincode_trait_derive::{BorrowDecodeFromDecode, Decode, Encode};
+
+pub struct Ring {
+    pub modulus: u64,
+}
+
+#[derive(Debug, PartialEq, Encode, Decode, BorrowDecodeFromDecode)]
+#[trait_decode(
+    context_type = Ring,
+    pre_encode = Polynomial::check_sorted,
+    validate = Polynomial::check,
+)]
+pub struct Polynomial {
+    pub exponents: Vec<u16>,
+    pub coefficients: Vec<u64>,
+}
+
+impl Polynomial {
+    fn check_sorted(&self) -> Result<(), String> {
+        if self.exponents.is_sorted() {
+            Ok(())
+        } else {
+            Err("exponents are not sorted".to_string())
+        }
+    }
+
+    fn check(&self, ring: &Ring) -> Result<(), String> {
+        self.check_sorted()?;
+        match self.coefficients.iter().find(|&&c| c >= ring.modulus) {
+            Some(c) => Err(format!("coefficient {c} is not reduced")),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Counts the decoded values and scales them, to check that `finish` gets the context mutably.
+pub struct Session<'a> {
+    pub decoded: &'a Cell<usize>,
+    pub scale: u32,
+}
+
+#[derive(Debug, PartialEq, Encode, Decode, BorrowDecodeFromDecode)]
+#[trait_decode(context_type = Session<'a>, finish = Measurement::finish)]
+pub enum Measurement {
+    Raw(u32),
+    Scaled(u32),
+}
+
+impl Measurement {
+    fn finish(&mut self, session: &mut Session) {
+        session.decoded.set(session.decoded.get() + 1);
+        if let Measurement::Raw(value) = *self {
+            *self = Measurement::Scaled(value * session.scale);
+        }
+    }
+}
+
+fn encode<T: bincode::Encode>(value: T) -> Result<Vec<u8>, EncodeError> {
+    bincode::encode_to_vec(value, bincode::config::standard())
+}
+
+#[test]
+fn test_validate() {
+    let polynomial = Polynomial {
+        exponents: vec![0, 1, 3],
+        coefficients: vec![1, 4, 6],
+    };
+    let encoded = encode(&polynomial).unwrap();
+    let (decoded, _): (Polynomial, usize) = bincode::borrow_decode_from_slice_with_context(
+        &encoded,
+        bincode::config::standard(),
+        Ring { modulus: 7 },
+    )
+    .unwrap();
+    assert_eq!(decoded, polynomial);
+
+    let err = bincode::decode_from_slice_with_context::<_, Polynomial, _>(
+        &encoded,
+        bincode::config::standard(),
+        Ring { modulus: 5 },
+    )
+    .unwrap_err();
+    match err {
+        DecodeError::OtherString(message) => {
+            assert_eq!(
+                message,
+                "invalid `Polynomial`: coefficient 6 is not reduced"
+            )
+        }
+        err => panic!("unexpected error {err:?}"),
+    }
+}
+
+#[test]
+fn test_pre_encode() {
+    let polynomial = Polynomial {
+        exponents: vec![3, 1],
+        coefficients: vec![1, 2],
+    };
+    match encode(&polynomial).unwrap_err() {
+        EncodeError::OtherString(message) => {
+            assert_eq!(message, "invalid `Polynomial`: exponents are not sorted")
+        }
+        err => panic!("unexpected error {err:?}"),
+    }
+}
+
+#[test]
+fn test_finish() {
+    let decoded = Cell::new(0);
+    let encoded = encode(Measurement::Raw(3)).unwrap();
+    let session = Session {
+        decoded: &decoded,
+        scale: 10,
+    };
+    let (measurement, _): (Measurement, usize) =
+        bincode::decode_from_slice_with_context(&encoded, bincode::config::standard(), session)
+            .unwrap();
+    assert_eq!(measurement, Measurement::Scaled(30));
+    assert_eq!(decoded.get(), 1);
+}
@@ -0,0 +1,160 @@
+use std::collections::HashMap;
+
+use bincode::de::Decoder;
+use bincode::enc::Encoder;
+use bincode::error::{DecodeError, EncodeError};
+use bincode_trait_derive::{BorrowDecodeFromDecode, Decode, Encode};
+
+// Neither Encode nor Decode: only written through the `species` hooks
+#[derive(Debug, Clone, PartialEq)]
+pub struct Species {
+    pub id: u32,
+    pub name: String,
+}
+
+pub trait SpeciesTable {
+    fn species(&self, id: u32) -> Option<&Species>;
+}
+
+pub struct Catalogue {
+    pub species: HashMap<u32, Species>,
+}
+
+impl SpeciesTable for Catalogue {
+    fn species(&self, id: u32) -> Option<&Species> {
+        self.species.get(&id)
+    }
+}
+
+/// Writes a species as its id and looks it up in the context when decoding.
+mod species {
+    use super::*;
+
+    pub fn encode<E: Encoder>(species: &Species, encoder: &mut E) -> Result<(), EncodeError> {
+        bincode::Encode::encode(&species.id, encoder)
+    }
+
+    pub fn decode<C: SpeciesTable, D: Decoder<Context = C>>(
+        decoder: &mut D,
+    ) -> Result<Species, DecodeError> {
+        let id: u32 = bincode::Decode::decode(decoder)?;
+        decoder
+            .context()
+            .species(id)
+            .cloned()
+            .ok_or(DecodeError::Other("unknown species"))
+    }
+}
+
+/// Writes a count as a `u8`.
+mod narrow {
+    use super::*;
+
+    pub fn encode<E: Encoder>(count: &u32, encoder: &mut E) -> Result<(), EncodeError> {
+        let count = u8::try_from(*count).map_err(|_| EncodeError::Other("count out of range"))?;
+        bincode::Encode::encode(&count, encoder)
+    }
+}
+
+#[derive(Debug, PartialEq, Encode, Decode, BorrowDecodeFromDecode)]
+#[trait_decode(trait = SpeciesTable)]
+pub struct Fish {
+    #[trait_decode(with = species)]
+    pub species: Species,
+    pub length: u32,
+}
+
+#[derive(Debug, PartialEq, Encode, Decode, BorrowDecodeFromDecode)]
+#[trait_decode(context_type = Catalogue)]
+pub enum Catch {
+    Fish(#[trait_decode(with = species)] Species, u32),
+    School {
+        #[trait_decode(with = species)]
+        species: Species,
+        #[trait_decode(encode_with = narrow, decode_with = narrow_decode)]
+        count: u32,
+    },
+    Nothing,
+}
+
+/// Reads the `u8` written by `narrow::encode`.
+mod narrow_decode {
+    use super::*;
+
+    pub fn decode<C, D: Decoder<Context = C>>(decoder: &mut D) -> Result<u32, DecodeError> {
+        let count: u8 = bincode::Decode::decode(decoder)?;
+        Ok(count.into())
+    }
+}
+
+fn catalogue() -> Catalogue {
+    let trout = Species {
+        id: 7,
+        name: "trout".to_string(),
+    };
+    Catalogue {
+        species: HashMap::from([(trout.id, trout)]),
+    }
+}
+
+fn trout() -> Species {
+    catalogue().species[&7].clone()
+}
+
+#[test]
+fn test_with_hooks_on_struct() {
+    let fish = Fish {
+        species: trout(),
+        length: 30,
+    };
+    let encoded = bincode::encode_to_vec(&fish, bincode::config::standard()).unwrap();
+    assert_eq!(
+        encoded,
+        bincode::encode_to_vec((7u32, 30u32), bincode::config::standard()).unwrap()
+    );
+
+    let (decoded, _): (Fish, usize) = bincode::borrow_decode_from_slice_with_context(
+        &encoded,
+        bincode::config::standard(),
+        catalogue(),
+    )
+    .unwrap();
+    assert_eq!(decoded, fish);
+
+    let encoded = bincode::encode_to_vec((8u32, 30u32), bincode::config::standard()).unwrap();
+    let err = bincode::decode_from_slice_with_context::<_, Fish, _>(
+        &encoded,
+        bincode::config::standard(),
+        catalogue(),
+    )
+    .unwrap_err();
+    assert!(matches!(err, DecodeError::Other("unknown species")));
+}
+
+#[test]
+fn test_with_hooks_on_variant_fields() {
+    for catch in [
+        Catch::Fish(trout(), 12),
+        Catch::School {
+            species: trout(),
+            count: 200,
+        },
+        Catch::Nothing,
+    ] {
+        let encoded = bincode::encode_to_vec(&catch, bincode::config::standard()).unwrap();
+        let (decoded, _): (Catch, usize) = bincode::decode_from_slice_with_context(
+            &encoded,
+            bincode::config::standard(),
+            catalogue(),
+        )
+        .unwrap();
+        assert_eq!(decoded, catch);
+    }
+
+    let school = Catch::School {
+        species: trout(),
+        count: 300,
+    };
+    let err = bincode::encode_to_vec(&school, bincode::config::standard()).unwrap_err();
+    assert!(matches!(err, EncodeError::Other("count out of range")));
+}